@@ -0,0 +1,118 @@
+//! Allophones, the variants of a phoneme chosen by its context.
+//!
+//! A voice may have several phonemes for one sound, such as an aspirated
+//! `tʰ` and a flapped `ɾ` as well as a plain `t`. Rules replace the plain
+//! phoneme with a variant according to its neighbours in the same word,
+//! before the segments are played.
+//!
+//! The neighbours are matched by patterns, which are a name, a kind (such as
+//! `vowel`), `*` for any phoneme, or `#` for the edge of the word. A leading
+//! `!` negates a pattern. The first matching rule whose allophone is in the
+//! voice is used, so voices without variants are unaffected. An empty
+//! allophone removes the phoneme, which is useful for doubled consonants.
+//!
+//! Rules are applied from left to right, so the previous phoneme is the one
+//! already chosen, and the next is the original.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    phonemes::{self, Phoneme},
+    utterance::{self, Segment},
+    voice::LpcVoice,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rule {
+    pub phoneme: String,
+    pub allophone: String,
+    /// Pattern for the previous phoneme, or any if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev: Option<String>,
+    /// Pattern for the next phoneme, or any if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+/// The rules used when a voice doesn't have its own, as phoneme, allophone,
+/// and patterns for the previous and next phonemes.
+const BUILTIN: &[(&str, &str, &str, &str)] = &[
+    // Doubled stops, as in the spelling of "butter", are one stop.
+    ("t", "", "*", "t"),
+    ("d", "", "*", "d"),
+    ("k", "", "*", "k"),
+    ("g", "", "*", "g"),
+    ("p", "", "*", "p"),
+    ("b", "", "*", "b"),
+    // Flapping, as in "butter".
+    ("t", "ɾ", "vowel", "vowel"),
+    ("d", "ɾ", "vowel", "vowel"),
+    // Aspiration at the start of a word.
+    ("t", "tʰ", "#", "vowel"),
+    ("k", "kʰ", "#", "vowel"),
+    ("p", "pʰ", "#", "vowel"),
+    // Unreleased stops at the end of a word.
+    ("t", "t̚", "*", "#"),
+    ("k", "k̚", "*", "#"),
+    ("p", "p̚", "*", "#"),
+    // Dark l after a vowel, at the end of a syllable.
+    ("l", "ɫ", "vowel", "!vowel"),
+];
+
+pub fn builtin() -> Vec<Rule> {
+    let pattern = |p: &str| (p != "*").then(|| p.to_string());
+    BUILTIN
+        .iter()
+        .map(|(phoneme, allophone, prev, next)| Rule {
+            phoneme: phoneme.to_string(),
+            allophone: allophone.to_string(),
+            prev: pattern(prev),
+            next: pattern(next),
+        })
+        .collect()
+}
+
+/// Whether a neighbour, with its name, matches a pattern.
+fn matches(pattern: &str, neighbour: Option<(&str, &Phoneme)>) -> bool {
+    if let Some(pattern) = pattern.strip_prefix('!') {
+        return !matches(pattern, neighbour);
+    }
+    match neighbour {
+        Some((name, phoneme)) => phonemes::matches(pattern, name, phoneme),
+        None => pattern == "#",
+    }
+}
+
+/// Replace phonemes of a voice with their allophones.
+pub fn choose<'a>(
+    rules: &[Rule],
+    voice: &'a LpcVoice,
+    segments: Vec<Segment<&'a Phoneme>>,
+) -> Vec<Segment<&'a Phoneme>> {
+    let neighbour = |phoneme: Option<&&'a Phoneme>| phoneme.map(|p| (voice.name(p), *p));
+    let mut result = Vec::with_capacity(segments.len());
+    for (ix, segment) in segments.iter().enumerate() {
+        let &Segment::Phone(phoneme, prosody) = segment else {
+            result.push(segment.clone());
+            continue;
+        };
+        let name = voice.name(phoneme);
+        let prev = neighbour(utterance::adjacent(result.iter().rev()));
+        let next = neighbour(utterance::adjacent(segments[ix + 1..].iter()));
+        let allophone = rules
+            .iter()
+            .filter(|rule| rule.phoneme == name)
+            .filter(|rule| rule.prev.as_ref().is_none_or(|p| matches(p, prev)))
+            .filter(|rule| rule.next.as_ref().is_none_or(|p| matches(p, next)))
+            .find_map(|rule| match rule.allophone.as_str() {
+                "" => Some(None),
+                name => voice.get(name).map(Some),
+            });
+        match allophone {
+            Some(Some(allophone)) => result.push(Segment::Phone(allophone, prosody)),
+            Some(None) => {}
+            None => result.push(segment.clone()),
+        }
+    }
+    result
+}
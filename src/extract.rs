@@ -0,0 +1,118 @@
+//! Extraction of LPC phonemes from a labelled recording.
+//!
+//! All intervals with the same label are analyzed together, and the
+//! reflection coefficients and RMS are averaged over their frames. The kind
+//! and voicing of a phoneme are taken from the built-in table if it has one
+//! of the same name, otherwise voicing is detected from the analysis.
+
+use std::borrow::Cow;
+
+use crate::{
+    labels::Label,
+    lpc::{self, Reflector},
+    phonemes::{self, Kind, Phoneme},
+};
+
+/// The sample rate of the LPC synthesizer, which the recording must match.
+pub const SAMPLE_RATE: u32 = 16_000;
+
+const FRAME_SIZE: usize = 400;
+const WINDOW_SIZE: usize = 800;
+/// Intervals shorter than this are analyzed as a single window.
+const MIN_WINDOW_SIZE: usize = 160;
+
+/// Build the phonemes of an LPC voice from a recording and its labels.
+///
+/// Unlabelled intervals (those with an empty name) are ignored.
+pub fn extract(samples: &[f64], labels: &[Label], preemph: f64) -> Vec<(String, Phoneme)> {
+    let emphasized = lpc::preemph(samples, preemph);
+    let mut names: Vec<&str> = vec![];
+    for label in labels {
+        if !label.name.is_empty() && !names.contains(&label.name.as_str()) {
+            names.push(&label.name);
+        }
+    }
+    let mut result = vec![];
+    for name in names {
+        let windows = labels
+            .iter()
+            .filter(|label| label.name == name)
+            .flat_map(|label| windows(label, samples.len()))
+            .collect::<Vec<_>>();
+        if windows.is_empty() {
+            eprintln!("{name}: no intervals long enough to analyze");
+            continue;
+        }
+        // Durations, noise and releases aren't measured, so come from the
+        // built-in phoneme.
+        let (kind, voiced, noise, duration, stop) = match phonemes::get_phoneme(name) {
+            Some(ph) => (ph.kind, ph.voiced, ph.noise, ph.duration, ph.stop),
+            None => {
+                let n_voiced = windows
+                    .iter()
+                    .filter(|w| !Reflector::new(&samples[(*w).clone()]).is_unvoiced())
+                    .count();
+                let voiced = n_voiced * 2 > windows.len();
+                let kind = if voiced { Kind::Vowel } else { Kind::Fricative };
+                (kind, voiced, 0.0, None, None)
+            }
+        };
+        // As in the `lpc` command, voiced sounds are analyzed with pre-emphasis.
+        let buf = if voiced { &emphasized } else { samples };
+        let mut ks = vec![];
+        let mut power = 0.0;
+        let mut n = 0;
+        for window in windows {
+            let coeffs = Reflector::new(&buf[window]);
+            if !coeffs.rms().is_finite() || coeffs.ks().iter().any(|k| !k.is_finite()) {
+                // Digital silence.
+                continue;
+            }
+            ks.resize(coeffs.ks().len(), 0.0);
+            for (k, x) in ks.iter_mut().zip(coeffs.ks()) {
+                *k += x;
+            }
+            power += coeffs.rms().powi(2);
+            n += 1;
+        }
+        if n == 0 {
+            eprintln!("{name}: only silence");
+            continue;
+        }
+        for k in &mut ks {
+            *k /= n as f64;
+        }
+        let mut rms = (power / n as f64).sqrt();
+        if kind == Kind::Fricative {
+            rms *= phonemes::UNVOICED_MUL;
+        }
+        let phoneme = Phoneme {
+            kind,
+            voiced,
+            ks: Cow::Owned(ks),
+            rms,
+            noise,
+            duration,
+            glide: None,
+            stop,
+        };
+        result.push((name.to_string(), phoneme));
+    }
+    result
+}
+
+/// The analysis windows within a labelled interval.
+fn windows(label: &Label, len: usize) -> Vec<std::ops::Range<usize>> {
+    let to_sample = |t: f64| ((t * SAMPLE_RATE as f64).round().max(0.) as usize).min(len);
+    let (start, end) = (to_sample(label.start), to_sample(label.end));
+    let n = end.saturating_sub(start);
+    if n < MIN_WINDOW_SIZE {
+        vec![]
+    } else if n < WINDOW_SIZE {
+        std::iter::once(start..end).collect()
+    } else {
+        (0..=(n - WINDOW_SIZE) / FRAME_SIZE)
+            .map(|i| start + i * FRAME_SIZE..start + i * FRAME_SIZE + WINDOW_SIZE)
+            .collect()
+    }
+}
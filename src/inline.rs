@@ -0,0 +1,168 @@
+//! Inline phoneme and control escapes in plain text.
+//!
+//! This is a lightweight alternative to SSML for fixing individual
+//! pronunciations in UI strings. Phonemes are written in concise IPA between
+//! double brackets, for example `[[ɛksəl]]`, and controls are written between
+//! backslashes:
+//!
+//! * `\pause=300\` inserts a pause, in ms (units as in SSML are accepted),
+//!   of at most [`utterance::MAX_BREAK`].
+//! * `\rate=1.2\` sets the speaking rate.
+//! * `\pitch=+2st\` sets the pitch.
+//! * `\volume=-6dB\` sets the volume.
+//! * `\mark=name\` inserts a named mark.
+//! * `\reset\` restores the default rate, pitch and volume.
+//! * `\\` is a literal backslash.
+//!
+//! Values are parsed as the corresponding SSML attributes, and are relative
+//! to the voice defaults rather than cumulative. A backslash which doesn't
+//! start a control is read as text.
+
+use core::fmt;
+
+use crate::{
+    ssml,
+    text_to_phoneme::TextToPhoneme,
+    utterance::{self, Item, Prosody},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// A `[[` without a matching terminator.
+    Unterminated,
+    /// A control value that could not be understood.
+    BadValue,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unterminated => write!(f, "unterminated escape"),
+            Error::BadValue => write!(f, "invalid control value"),
+        }
+    }
+}
+
+/// The names of controls, so that other backslashes can be read as text.
+const CONTROLS: &[&str] = &["pause", "mark", "rate", "pitch", "volume", "reset"];
+
+/// Convert text with inline escapes to items.
+pub fn parse(ttp: &TextToPhoneme, s: &str) -> Result<Vec<Item>, Error> {
+    let mut items = Vec::new();
+    let mut prosody = Prosody::default();
+    // Start of the text not yet converted, and of the search for escapes.
+    let mut text_start = 0;
+    let mut ix = 0;
+    while let Some(found) = s[ix..].find(['[', '\\']) {
+        let start = ix + found;
+        let text = &s[text_start..start];
+        let escape = &s[start..];
+        if let Some(body) = escape.strip_prefix("[[") {
+            utterance::from_text(ttp, text, text_start, false, &mut items);
+            let (phonemes, _) = body.split_once("]]").ok_or(Error::Unterminated)?;
+            let src_start = start + 2;
+            items.push(Item::Word {
+                phonemes: phonemes.to_string(),
+                src: src_start..src_start + phonemes.len(),
+            });
+            ix = src_start + phonemes.len() + 2;
+            text_start = ix;
+        } else if escape.starts_with("\\\\") {
+            // The first backslash is kept as text.
+            utterance::from_text(
+                ttp,
+                &s[text_start..start + 1],
+                text_start,
+                false,
+                &mut items,
+            );
+            ix = start + 2;
+            text_start = ix;
+        } else if let Some(body) = escape.strip_prefix('\\')
+            && let Some((control, _)) = body.split_once('\\')
+            && is_control(control)
+        {
+            utterance::from_text(ttp, text, text_start, false, &mut items);
+            control_item(control, &mut prosody, &mut items)?;
+            ix = start + control.len() + 2;
+            text_start = ix;
+        } else {
+            // A single bracket, or a backslash not starting a control, is
+            // just text.
+            ix = start + 1;
+        }
+    }
+    utterance::from_text(ttp, &s[text_start..], text_start, false, &mut items);
+    Ok(items)
+}
+
+fn is_control(control: &str) -> bool {
+    let name = control.split_once('=').map_or(control, |(name, _)| name);
+    CONTROLS.contains(&name.trim())
+}
+
+fn control_item(control: &str, prosody: &mut Prosody, items: &mut Vec<Item>) -> Result<(), Error> {
+    let (name, value) = control.split_once('=').unwrap_or((control, ""));
+    let value = value.trim();
+    match name.trim() {
+        "pause" => {
+            let ms = match value.parse::<u32>() {
+                Ok(ms) => ms.min(utterance::MAX_BREAK),
+                Err(_) => ssml::parse_time(value).ok_or(Error::BadValue)?,
+            };
+            items.push(Item::Break(ms));
+            return Ok(());
+        }
+        "mark" => {
+            items.push(Item::Mark(value.to_string()));
+            return Ok(());
+        }
+        "rate" => prosody.rate = ssml::parse_rate(value).ok_or(Error::BadValue)?,
+        "pitch" => prosody.pitch = ssml::parse_pitch(value).ok_or(Error::BadValue)?,
+        "volume" => prosody.volume = ssml::parse_volume(value).ok_or(Error::BadValue)?,
+        "reset" => *prosody = Prosody::default(),
+        _ => unreachable!(),
+    }
+    items.push(Item::Prosody(*prosody));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaks(text: &str) -> Vec<u32> {
+        let items = parse(&TextToPhoneme::new(), text).unwrap();
+        (items.iter())
+            .filter_map(|item| match item {
+                Item::Break(ms) => Some(*ms),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pause() {
+        assert_eq!(breaks("a\\pause=300\\b"), [300]);
+        assert_eq!(breaks("a\\pause=1.5s\\b"), [1500]);
+        assert_eq!(breaks("a\\pause=300000000\\b"), [utterance::MAX_BREAK]);
+        assert_eq!(breaks("a\\pause=99999s\\b"), [utterance::MAX_BREAK]);
+        let ttp = TextToPhoneme::new();
+        for text in ["\\pause=-1s\\", "\\pause=soon\\"] {
+            assert_eq!(parse(&ttp, text).err(), Some(Error::BadValue), "{text}");
+        }
+    }
+
+    #[test]
+    fn stray_backslash() {
+        let text = "C:\\Users \\\\ x\\";
+        let items = parse(&TextToPhoneme::new(), text).unwrap();
+        let words = (items.iter())
+            .filter_map(|item| match item {
+                Item::Word { src, .. } => Some(&text[src.clone()]),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(words, ["C", "Users", "x"]);
+    }
+}
@@ -0,0 +1,139 @@
+//! Reading of label files, as produced by annotation tools.
+//!
+//! Two formats are supported: Audacity label tracks (exported as text), and
+//! Praat TextGrids in either the long or the short text format.
+
+/// A labelled interval, with times in seconds.
+pub struct Label {
+    pub start: f64,
+    pub end: f64,
+    pub name: String,
+}
+
+/// Parse a label file, detecting the format.
+pub fn parse(s: &str) -> Option<Vec<Label>> {
+    if s.contains("ooTextFile") {
+        parse_textgrid(s)
+    } else {
+        Some(parse_audacity(s))
+    }
+}
+
+/// Parse an Audacity label track.
+///
+/// Each line has a start time, end time and name, separated by tabs. Lines
+/// which don't match, such as the frequency ranges of spectral selections,
+/// are ignored.
+pub fn parse_audacity(s: &str) -> Vec<Label> {
+    let mut result = vec![];
+    for line in s.lines() {
+        let mut fields = line.splitn(3, '\t');
+        if let Some(start) = fields.next().and_then(|f| f.trim().parse().ok())
+            && let Some(end) = fields.next().and_then(|f| f.trim().parse().ok())
+        {
+            let name = fields.next().unwrap_or_default().trim().to_string();
+            result.push(Label { start, end, name });
+        }
+    }
+    result
+}
+
+enum Token {
+    Number(f64),
+    Text(String),
+}
+
+/// Split a TextGrid into numbers and strings.
+///
+/// With the keys (`xmin =`), indices (`[1]`) and comments dropped, the long
+/// and short formats are the same sequence of tokens.
+fn textgrid_tokens(s: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut text = String::new();
+                while let Some(c) = chars.next() {
+                    if c == '"' {
+                        // A doubled quote is an escaped quote.
+                        if chars.next_if_eq(&'"').is_none() {
+                            break;
+                        }
+                    }
+                    text.push(c);
+                }
+                tokens.push(Token::Text(text));
+            }
+            '[' => while chars.next().is_some_and(|c| c != ']') {},
+            '!' => while chars.next().is_some_and(|c| c != '\n') {},
+            '0'..='9' | '-' | '.' => {
+                let mut num = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+                    num.push(c);
+                }
+                if let Ok(x) = num.parse() {
+                    tokens.push(Token::Number(x));
+                }
+            }
+            c if c.is_alphabetic() => {
+                // Skip the rest of an identifier, so digits in it aren't numbers.
+                while chars
+                    .next_if(|c| c.is_alphanumeric() || *c == '_')
+                    .is_some()
+                {}
+            }
+            _ => (),
+        }
+    }
+    tokens
+}
+
+fn text(tokens: &mut impl Iterator<Item = Token>) -> Option<String> {
+    match tokens.next()? {
+        Token::Text(s) => Some(s),
+        Token::Number(_) => None,
+    }
+}
+
+fn number(tokens: &mut impl Iterator<Item = Token>) -> Option<f64> {
+    match tokens.next()? {
+        Token::Number(x) => Some(x),
+        Token::Text(_) => None,
+    }
+}
+
+/// Parse a Praat TextGrid, returning the intervals of the first interval tier.
+pub fn parse_textgrid(s: &str) -> Option<Vec<Label>> {
+    let tokens = &mut textgrid_tokens(s).into_iter();
+    if text(tokens)? != "ooTextFile" || text(tokens)? != "TextGrid" {
+        return None;
+    }
+    // xmin, xmax and the number of tiers.
+    number(tokens)?;
+    number(tokens)?;
+    let n_tiers = number(tokens)? as usize;
+    for _ in 0..n_tiers {
+        let class = text(tokens)?;
+        let _name = text(tokens)?;
+        number(tokens)?;
+        number(tokens)?;
+        let size = number(tokens)? as usize;
+        if class == "IntervalTier" {
+            let mut result = Vec::with_capacity(size);
+            for _ in 0..size {
+                let start = number(tokens)?;
+                let end = number(tokens)?;
+                let name = text(tokens)?.trim().to_string();
+                result.push(Label { start, end, name });
+            }
+            return Some(result);
+        }
+        // Point tiers have a time and a mark for each point.
+        for _ in 0..size {
+            number(tokens)?;
+            text(tokens)?;
+        }
+    }
+    None
+}
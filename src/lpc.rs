@@ -1,11 +1,46 @@
-const ORDER: usize = 18;
+//! Linear prediction analysis.
+//!
+//! The filter is found from a window of samples by one of three methods.
+//! The autocorrelation method always gives a stable filter, and is the
+//! default. The covariance method doesn't assume the signal is zero outside
+//! the window, so is more accurate for short windows, but can be unstable.
+//! Burg's method estimates the reflection coefficients directly from the
+//! forward and backward prediction errors, and is always stable.
+
+use std::f64::consts::PI;
+
+use clap::ValueEnum;
+
+/// The default order of analysis.
+pub const ORDER: usize = 18;
 
 #[derive(Default)]
 pub struct Reflector {
-    ks: [f64; ORDER],
+    ks: Vec<f64>,
     rms: f64,
 }
 
+/// A method of LPC analysis.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Method {
+    Autocorrelation,
+    Covariance,
+    Burg,
+}
+
+/// A window function, applied before analysis.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Window {
+    Rectangular,
+    Hamming,
+    Hann,
+}
+
+/// Reflection coefficients are clamped to this magnitude when the
+/// covariance method gives an unstable filter. If they can't be found at
+/// all, the autocorrelation method is used instead.
+const MAX_K: f64 = 0.999;
+
 fn get_correlation(buf: &[f64], lag: usize) -> f64 {
     let mut sum = 0.0;
     for i in 0..buf.len() - lag {
@@ -14,31 +49,140 @@ fn get_correlation(buf: &[f64], lag: usize) -> f64 {
     sum
 }
 
-fn get_correlations(buf: &[f64]) -> [f64; ORDER + 1] {
-    core::array::from_fn(|lag| get_correlation(buf, lag))
+fn get_correlations(buf: &[f64], order: usize) -> Vec<f64> {
+    (0..=order).map(|lag| get_correlation(buf, lag)).collect()
+}
+
+impl Window {
+    /// Apply the window, scaled so that it doesn't change the RMS of a
+    /// stationary signal.
+    pub fn apply(self, buf: &[f64]) -> Vec<f64> {
+        use std::f64::consts::TAU;
+        let n = buf.len();
+        let w = |i: usize| {
+            let x = TAU * i as f64 / (n - 1).max(1) as f64;
+            match self {
+                Window::Rectangular => 1.0,
+                Window::Hamming => 0.54 - 0.46 * x.cos(),
+                Window::Hann => 0.5 - 0.5 * x.cos(),
+            }
+        };
+        let power = (0..n).map(|i| w(i) * w(i)).sum::<f64>() / n as f64;
+        let scale = 1.0 / power.sqrt();
+        buf.iter()
+            .enumerate()
+            .map(|(i, x)| x * w(i) * scale)
+            .collect()
+    }
+}
+
+/// Convert reflection coefficients to predictor coefficients.
+///
+/// The result includes the leading 1.
+pub fn predictor(ks: &[f64]) -> Vec<f64> {
+    let mut a = vec![1.0];
+    for &k in ks {
+        let prev = a.clone();
+        a.push(0.0);
+        for i in 1..a.len() {
+            a[i] = prev.get(i).copied().unwrap_or_default() + k * prev[a.len() - 1 - i];
+        }
+    }
+    a
+}
+
+/// The gain of a filter at a frequency in Hz, in dB.
+pub fn envelope(ks: &[f64], freq: f64, sample_rate: u32) -> f64 {
+    let omega = 2.0 * PI * freq / sample_rate as f64;
+    let (mut re, mut im) = (0.0, 0.0);
+    for (i, a) in predictor(ks).iter().enumerate() {
+        re += a * (i as f64 * omega).cos();
+        im -= a * (i as f64 * omega).sin();
+    }
+    -10.0 * (re * re + im * im).log10()
+}
+
+/// Estimate the first formant of a filter, in Hz, as the lowest peak of its
+/// spectrum.
+///
+/// This is much cheaper than finding the roots of the predictor, but only
+/// coarse. Returns `None` if there's no peak in the range of F1.
+pub fn first_formant(ks: &[f64], sample_rate: u32) -> Option<f64> {
+    const MIN_F1: f64 = 150.0;
+    const MAX_F1: f64 = 1200.0;
+    const STEP: f64 = 25.0;
+    let n = ((MAX_F1 - MIN_F1) / STEP) as usize;
+    let gains = (0..=n)
+        .map(|i| envelope(ks, MIN_F1 + i as f64 * STEP, sample_rate))
+        .collect::<Vec<_>>();
+    (1..n)
+        .find(|&i| gains[i] > gains[i - 1] && gains[i] >= gains[i + 1])
+        .map(|i| MIN_F1 + i as f64 * STEP)
+}
+
+/// Convert predictor coefficients, including the leading 1, to reflection
+/// coefficients.
+///
+/// The result is only meaningful if the filter is stable.
+pub fn reflection(a: &[f64]) -> Vec<f64> {
+    let order = a.len() - 1;
+    let mut a = a.to_vec();
+    let mut ks = vec![0.0; order];
+    for m in (1..=order).rev() {
+        let k = a[m];
+        ks[m - 1] = k;
+        let scale = 1.0 / (1.0 - k * k);
+        let prev = a.clone();
+        for i in 1..m {
+            a[i] = (prev[i] - k * prev[m - i]) * scale;
+        }
+    }
+    ks
 }
 
-#[allow(unused)]
+/// Apply a pre-emphasis filter, boosting high frequencies.
+pub fn preemph(inp: &[f64], a: f64) -> Vec<f64> {
+    (0..inp.len())
+        .map(|i| inp[i] - inp.get(i.wrapping_sub(1)).cloned().unwrap_or_default() * a)
+        .collect()
+}
+
+/// Normalized autocorrelation at the given lag.
 pub fn confidence(buf: &[f64], period: usize) -> f64 {
     get_correlation(buf, period) / get_correlation(buf, 0)
 }
 
 impl Reflector {
+    /// Analyze by the autocorrelation method, with the default order.
     pub fn new(buf: &[f64]) -> Self {
-        let coeffs = get_correlations(buf);
-        let mut result = Self::default();
-        result.translate_coeffs(&coeffs, buf.len());
-        result
+        Self::analyze(buf, ORDER, Method::Autocorrelation)
+    }
+
+    pub fn analyze(buf: &[f64], order: usize, method: Method) -> Self {
+        match method {
+            Method::Autocorrelation => {
+                let coeffs = get_correlations(buf, order);
+                let mut result = Self {
+                    ks: vec![0.0; order],
+                    rms: 0.0,
+                };
+                result.translate_coeffs(&coeffs, buf.len());
+                result
+            }
+            Method::Covariance => Self::covariance(buf, order),
+            Method::Burg => Self::burg(buf, order),
+        }
     }
 
     fn translate_coeffs(&mut self, coeffs: &[f64], n_samples: usize) {
-        let mut b = [0.0; ORDER];
-        let mut d = [0.0; ORDER + 1];
+        let order = self.ks.len();
+        let mut b = vec![0.0; order];
+        let mut d = vec![0.0; order + 1];
         self.ks[0] = -coeffs[1] / coeffs[0];
         d[0] = coeffs[1];
         d[1] = coeffs[0] + (self.ks[0] * coeffs[1]);
 
-        for i in 1..ORDER {
+        for i in 1..order {
             let mut y = coeffs[i + 1];
             b[0] = y;
             for j in 0..i {
@@ -50,15 +194,106 @@ impl Reflector {
             d[i + 1] = d[i] + self.ks[i] * y;
             d[i] = b[i];
         }
-        self.rms = (d[ORDER] / n_samples as f64).sqrt();
+        self.rms = (d[order] / n_samples as f64).sqrt();
+    }
+
+    /// The covariance method, solving the normal equations by Cholesky
+    /// decomposition.
+    fn covariance(buf: &[f64], order: usize) -> Self {
+        let n = buf.len();
+        let phi = |i: usize, j: usize| (order..n).map(|t| buf[t - i] * buf[t - j]).sum::<f64>();
+        // Lower triangular factor of the matrix phi(i, j) for i, j in 1..=order.
+        let mut l = vec![vec![0.0; order]; order];
+        let mut singular = false;
+        for i in 0..order {
+            for j in 0..=i {
+                let sum = phi(i + 1, j + 1) - (0..j).map(|k| l[i][k] * l[j][k]).sum::<f64>();
+                if i == j {
+                    if sum <= 0.0 {
+                        singular = true;
+                        break;
+                    }
+                    l[i][i] = sum.sqrt();
+                } else {
+                    l[i][j] = sum / l[j][j];
+                }
+            }
+            if singular {
+                break;
+            }
+        }
+        if singular {
+            // Silence, or a signal too simple for this order.
+            return Self {
+                ks: vec![0.0; order],
+                rms: (phi(0, 0) / (n - order) as f64).sqrt(),
+            };
+        }
+        // Solve L L^T a = -phi(i, 0), by forward then back substitution.
+        let mut y = vec![0.0; order];
+        for i in 0..order {
+            y[i] = (-phi(i + 1, 0) - (0..i).map(|k| l[i][k] * y[k]).sum::<f64>()) / l[i][i];
+        }
+        let mut a = vec![0.0; order];
+        for i in (0..order).rev() {
+            a[i] = (y[i] - (i + 1..order).map(|k| l[k][i] * a[k]).sum::<f64>()) / l[i][i];
+        }
+        let error = phi(0, 0) + (0..order).map(|i| a[i] * phi(0, i + 1)).sum::<f64>();
+        let mut predictor = vec![1.0];
+        predictor.extend(&a);
+        let ks = reflection(&predictor);
+        if !ks.iter().all(|k| k.is_finite()) {
+            return Self::analyze(buf, order, Method::Autocorrelation);
+        }
+        Self {
+            ks: ks.iter().map(|k| k.clamp(-MAX_K, MAX_K)).collect(),
+            rms: (error.max(0.0) / (n - order) as f64).sqrt(),
+        }
+    }
+
+    /// Burg's method.
+    fn burg(buf: &[f64], order: usize) -> Self {
+        let mut f = buf.to_vec();
+        let mut b = buf.to_vec();
+        let mut error = buf.iter().map(|x| x * x).sum::<f64>() / buf.len() as f64;
+        let mut ks = vec![0.0; order];
+        for (m, k_out) in ks.iter_mut().enumerate() {
+            let mut num = 0.0;
+            let mut den = 0.0;
+            for t in m + 1..buf.len() {
+                num += f[t] * b[t - 1];
+                den += f[t] * f[t] + b[t - 1] * b[t - 1];
+            }
+            let k = if den > 0.0 { -2.0 * num / den } else { 0.0 };
+            for t in (m + 1..buf.len()).rev() {
+                let (ft, bt) = (f[t], b[t - 1]);
+                f[t] = ft + k * bt;
+                b[t] = bt + k * ft;
+            }
+            error *= 1.0 - k * k;
+            *k_out = k;
+        }
+        Self {
+            ks,
+            rms: error.sqrt(),
+        }
     }
 
-    #[allow(unused)]
     pub fn is_unvoiced(&self) -> bool {
         const UNVOICED_THRESHOLD: f64 = 0.3;
         self.ks[1] > UNVOICED_THRESHOLD
     }
 
+    /// True if the spectrum is dominated by low frequencies, as a voicing
+    /// cue for the pitch tracker.
+    ///
+    /// The first reflection coefficient is the negated normalized
+    /// autocorrelation at lag 1, so it's close to -1 for voiced sounds.
+    pub fn is_low_pass(&self) -> bool {
+        const LOW_PASS_THRESHOLD: f64 = 0.3;
+        self.ks[0] <= -LOW_PASS_THRESHOLD
+    }
+
     pub fn ks(&self) -> &[f64] {
         &self.ks
     }
@@ -1,42 +1,189 @@
-use std::f64::consts::PI;
+//! Formant estimation and tracking from LPC coefficients.
+//!
+//! The formants are the complex roots of the predictor polynomial, with the
+//! frequency given by the angle and the bandwidth by the distance from the
+//! unit circle. Not every root is a formant, and formants can merge or drop
+//! out, so the roots of each frame are assigned to F1–F5 by a tracker which
+//! prefers continuity with the previous frame.
+
+use std::{
+    f64::consts::PI,
+    io::{self, Write},
+};
 
 use rpoly::rpoly;
 
-pub fn lpc_to_formants(ks: &[f64]) {
-    const LEN: usize = 18;
-    // Levinson recursion, convert PARCORs to prediction coefficients
-    let mut alpha = [0.0; LEN + 1];
-    for i in 1..LEN + 1 {
-        alpha[i] = ks[i - 1];
-        let old_alpha = alpha;
-        for j in 1..i {
-            alpha[j] += ks[i - 1] * old_alpha[i - j];
-        }
+use crate::{lpc, vocoder};
+
+/// The largest number of coefficients supported.
+const MAX_ORDER: usize = 32;
+/// Roots with a wider bandwidth than this, in Hz, aren't formants.
+const MAX_BANDWIDTH: f64 = 600.0;
+/// Roots below this frequency, in Hz, are spectral tilt rather than formants.
+const MIN_FREQ: f64 = 90.0;
+
+pub const N_FORMANTS: usize = 5;
+/// Formant frequencies of a neutral vocal tract, in Hz.
+const NEUTRAL: [f64; N_FORMANTS] = [500.0, 1500.0, 2500.0, 3500.0, 4500.0];
+/// Cost of leaving a formant unassigned, relative to the cost of assigning a
+/// candidate, which is its log distance from the expected frequency.
+const MISS_COST: f64 = 1.0;
+/// Cost of a candidate which isn't assigned to a formant.
+const SKIP_COST: f64 = 0.3;
+/// Cost per Hz of bandwidth of a candidate.
+const BANDWIDTH_COST: f64 = 0.0005;
+/// Weight of the previous frame in the expected frequency, with the rest
+/// being the neutral frequency, so that errors don't persist.
+const PREV_WEIGHT: f64 = 0.8;
+
+/// A formant, with frequency and bandwidth in Hz.
+#[derive(Clone, Copy, Debug)]
+pub struct Formant {
+    pub freq: f64,
+    pub bandwidth: f64,
+}
+
+/// The predictor coefficients of a filter, padded to the largest order.
+///
+/// Missing coefficients are zero, which only adds roots at the origin.
+fn predictor(ks: &[f64]) -> [f64; MAX_ORDER + 1] {
+    assert!(ks.len() <= MAX_ORDER, "too many coefficients");
+    let mut alpha = [0.0; MAX_ORDER + 1];
+    for (alpha, a) in alpha.iter_mut().zip(lpc::predictor(ks)) {
+        *alpha = a;
     }
-    alpha[0] = 1.0;
-    println!("alpha {alpha:.3?}");
+    alpha
+}
+
+/// Find the formant candidates of a filter, in order of frequency.
+///
+/// Returns `None` if the roots of the predictor could not be found.
+pub fn lpc_to_formants(ks: &[f64], sample_rate: u32) -> Option<Vec<Formant>> {
+    let roots = rpoly(&predictor(ks)).ok()?;
+    let f_scale = sample_rate as f64 / (2.0 * PI);
     let mut formants = vec![];
-    if let Ok(roots) = rpoly(&alpha) {
-        for root in roots {
-            let (re, im) = (root.re, root.im);
-            if im > 0.0 {
-                let angle = im.atan2(re);
-                let f_scale = 16_000.0 / (2.0 * PI);
-                let r = im.hypot(re);
-                let f = angle * f_scale;
-                let bw = -2.0 * r.ln() * f_scale;
-                //println!("root {root:.3?} f {f:.3} bw {bw:.3} r {r:.3}");
-                const R_THRESH: f64 = 0.9;
-                if r > R_THRESH {
-                    formants.push((f, bw));
-                }
+    for root in roots {
+        let (re, im) = (root.re, root.im);
+        if im > 0.0 {
+            let freq = im.atan2(re) * f_scale;
+            let bandwidth = -2.0 * im.hypot(re).ln() * f_scale;
+            if freq > MIN_FREQ && bandwidth < MAX_BANDWIDTH {
+                formants.push(Formant { freq, bandwidth });
             }
         }
-        formants.sort_by_key(|(f, _bw)| f.to_bits());
-        for (f, bw) in formants {
-            println!("{f:.3} {bw:.3}");
+    }
+    formants.sort_by(|a, b| a.freq.total_cmp(&b.freq));
+    Some(formants)
+}
+
+/// Assigns formant candidates to F1–F5, frame by frame.
+#[derive(Default)]
+pub struct FormantTracker {
+    prev: [Option<f64>; N_FORMANTS],
+}
+
+impl FormantTracker {
+    /// Assign the candidates of a frame, in order of frequency.
+    ///
+    /// Each formant takes at most one candidate, in order, minimizing the
+    /// total distance from the expected frequencies. Formants which are
+    /// missing in this frame keep their previous value for the next.
+    pub fn track(&mut self, candidates: &[Formant]) -> [Option<Formant>; N_FORMANTS] {
+        let expected: [f64; N_FORMANTS] = std::array::from_fn(|j| match self.prev[j] {
+            Some(f) => PREV_WEIGHT * f + (1.0 - PREV_WEIGHT) * NEUTRAL[j],
+            None => NEUTRAL[j],
+        });
+        let cost = |c: &Formant, j: usize| {
+            (c.freq / expected[j]).ln().abs() + c.bandwidth * BANDWIDTH_COST
+        };
+        // dp[i][j] is the lowest cost of assigning the first j formants
+        // using the first i candidates.
+        let n = candidates.len();
+        let mut dp = vec![[0.0; N_FORMANTS + 1]; n + 1];
+        for (i, row) in dp.iter_mut().enumerate() {
+            row[0] = i as f64 * SKIP_COST;
+        }
+        dp[0] = std::array::from_fn(|j| j as f64 * MISS_COST);
+        for i in 1..=n {
+            for j in 1..=N_FORMANTS {
+                dp[i][j] = (dp[i - 1][j] + SKIP_COST)
+                    .min(dp[i][j - 1] + MISS_COST)
+                    .min(dp[i - 1][j - 1] + cost(&candidates[i - 1], j - 1));
+            }
+        }
+        let mut result = [None; N_FORMANTS];
+        let (mut i, mut j) = (n, N_FORMANTS);
+        while i > 0 && j > 0 {
+            if dp[i][j] == dp[i - 1][j] + SKIP_COST {
+                i -= 1;
+            } else if dp[i][j] == dp[i][j - 1] + MISS_COST {
+                j -= 1;
+            } else {
+                result[j - 1] = Some(candidates[i - 1]);
+                i -= 1;
+                j -= 1;
+            }
+        }
+        for (prev, formant) in self.prev.iter_mut().zip(&result) {
+            if let Some(formant) = formant {
+                *prev = Some(formant.freq);
+            }
+        }
+        result
+    }
+}
+
+/// A frame of a formant track.
+pub struct TrackFrame {
+    /// Time of the center of the frame, in seconds.
+    pub time: f64,
+    pub voiced: bool,
+    pub formants: [Option<Formant>; N_FORMANTS],
+}
+
+/// Track the formants of a recording.
+///
+/// Frames are spaced as for [`vocoder::analyze`], scaled to the sample rate.
+/// Silent frames have no formants.
+pub fn track(samples: &[f64], sample_rate: u32, preemph: f64) -> Vec<TrackFrame> {
+    let scale = |n: usize| n * sample_rate as usize / 16_000;
+    let hop = scale(vocoder::HOP);
+    let frames = vocoder::analyze(samples, sample_rate, preemph, hop, scale(vocoder::WINDOW));
+    let mut tracker = FormantTracker::default();
+    frames
+        .iter()
+        .enumerate()
+        .map(|(i, params)| {
+            let candidates = if params.rms > 0.0 {
+                lpc_to_formants(&params.k, sample_rate).unwrap_or_default()
+            } else {
+                vec![]
+            };
+            TrackFrame {
+                time: (i * hop + hop / 2) as f64 / sample_rate as f64,
+                voiced: params.period != 0.0,
+                formants: tracker.track(&candidates),
+            }
+        })
+        .collect()
+}
+
+/// Write a formant track as CSV, with empty fields for missing formants.
+pub fn write_csv(w: &mut impl Write, track: &[TrackFrame]) -> io::Result<()> {
+    write!(w, "time,voiced")?;
+    for i in 1..=N_FORMANTS {
+        write!(w, ",f{i},b{i}")?;
+    }
+    writeln!(w)?;
+    for frame in track {
+        write!(w, "{:.3},{}", frame.time, frame.voiced as u8)?;
+        for formant in &frame.formants {
+            match formant {
+                Some(f) => write!(w, ",{:.0},{:.0}", f.freq, f.bandwidth)?,
+                None => write!(w, ",,")?,
+            }
         }
-    } else {
-        println!("failed to find roots");
+        writeln!(w)?;
     }
+    Ok(())
 }
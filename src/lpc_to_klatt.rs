@@ -0,0 +1,196 @@
+//! Conversion of an LPC voice into a Klatt voice.
+//!
+//! The formants of each LPC phoneme replace those of the corresponding Klatt
+//! phone, and the amplitudes are adjusted to follow the levels and spectra of
+//! the recording. Everything else, including the phones which have no LPC
+//! counterpart, comes from the built-in Klatt voice.
+//!
+//! Only the steady state of a phoneme is recorded, so plosives and affricates,
+//! whose Klatt targets are loci rather than spectra, are left alone.
+
+use crate::{
+    extract::SAMPLE_RATE,
+    klatt::KlattParams,
+    lpc,
+    lpc_to_formants::{self, FormantTracker},
+    phonemes::{Kind, Phoneme},
+    phones::{ParamTarget, Phone},
+    voice::{KlattVoice, LpcVoice},
+};
+
+/// Range of bandwidths, in Hz, to accept from the analysis.
+const MIN_BANDWIDTH: f64 = 40.0;
+const MAX_BANDWIDTH: f64 = 500.0;
+/// Largest amplitude in the targets, in dB.
+const MAX_AMPLITUDE: f64 = 80.0;
+
+/// Convert an LPC voice into a Klatt voice.
+pub fn convert(lpc: &LpcVoice) -> KlattVoice {
+    let mut voice = KlattVoice::builtin();
+    let mut voiced = vec![];
+    let mut frication = vec![];
+    for (phone, phoneme) in phones(lpc) {
+        let Some(mut target) = voice.target(phone).cloned() else {
+            continue;
+        };
+        if target.af > 0 {
+            set_frication(&mut target, phoneme);
+            frication.push((phone, phoneme.rms));
+        } else {
+            let Some(candidates) = lpc_to_formants::lpc_to_formants(&phoneme.ks, SAMPLE_RATE)
+            else {
+                continue;
+            };
+            // The Klatt synthesizer has a fixed F5, which the others must be
+            // below.
+            let f5 = KlattParams::default().f5 as f64;
+            let mut tracker = FormantTracker::default();
+            let formants = tracker
+                .track(&candidates)
+                .map(|f| f.filter(|f| f.freq < f5));
+            let data = voice.phone_mut(phone);
+            let book = target.clone();
+            let freqs = [
+                &mut target.f1,
+                &mut target.f2,
+                &mut target.f3,
+                &mut target.f4,
+            ];
+            for (f, formant) in freqs.into_iter().zip(&formants) {
+                if let Some(formant) = formant {
+                    *f = formant.freq.round() as u16;
+                }
+            }
+            let bws = [&mut target.b1, &mut target.b2, &mut target.b3];
+            for (b, formant) in bws.into_iter().zip(&formants) {
+                if let Some(formant) = formant {
+                    *b = formant
+                        .bandwidth
+                        .clamp(MIN_BANDWIDTH, MAX_BANDWIDTH)
+                        .round() as u16;
+                }
+            }
+            // Take the end of a glide from that of a diphthong, tracking on
+            // from its start, or otherwise move it along with the start.
+            let end = (phoneme.glide.as_ref())
+                .and_then(|ks| lpc_to_formants::lpc_to_formants(ks, SAMPLE_RATE))
+                .map(|candidates| tracker.track(&candidates));
+            if let Some(glide) = &mut data.glide
+                && let Some([Some(f1), Some(f2), Some(f3), ..]) = end
+            {
+                glide.f1 = f1.freq.round() as u16;
+                glide.f2 = f2.freq.round() as u16;
+                glide.f3 = f3.freq.round() as u16;
+            } else if let Some(glide) = &mut data.glide {
+                let scale = |f: u16, new: u16, old: u16| {
+                    (f as f64 * new as f64 / old as f64).round() as u16
+                };
+                glide.f1 = scale(glide.f1, target.f1, book.f1);
+                glide.f2 = scale(glide.f2, target.f2, book.f2);
+                glide.f3 = scale(glide.f3, target.f3, book.f3);
+            }
+            if target.avc > 0 {
+                voiced.push((phone, phoneme.rms));
+            }
+        }
+        voice.phone_mut(phone).target = Some(target);
+    }
+    set_levels(&mut voice, &voiced, |t| [&mut t.av, &mut t.avc]);
+    set_levels(&mut voice, &frication, |t| [&mut t.af]);
+    voice
+}
+
+/// The Klatt phones with a phoneme in the LPC voice.
+///
+/// Where several phonemes map to the same phone, the first is used.
+fn phones(lpc: &LpcVoice) -> Vec<(Phone, &Phoneme)> {
+    let mut result: Vec<(Phone, &Phoneme)> = vec![];
+    for (name, phoneme) in lpc.iter() {
+        if matches!(phoneme.kind, Kind::Plosive | Kind::Affricate) {
+            continue;
+        }
+        if let [phone] = Phone::parse_ipa(name)[..]
+            && phone != Phone::Sil
+            && !result.iter().any(|(p, _)| *p == phone)
+        {
+            result.push((phone, phoneme));
+        }
+    }
+    result
+}
+
+/// Set the parallel formant amplitudes of a fricative from its spectrum.
+///
+/// Only the formants used by the built-in target are changed, and the
+/// loudest keeps its amplitude, so that the spectrum of the frication
+/// follows the recording but its overall level doesn't change.
+fn set_frication(target: &mut ParamTarget, phoneme: &Phoneme) {
+    let defaults = KlattParams::default();
+    let freqs = [
+        target.f2 as f64,
+        target.f3 as f64,
+        target.f4 as f64,
+        defaults.f5 as f64,
+        defaults.f6 as f64,
+    ];
+    let amps = [
+        &mut target.a2,
+        &mut target.a3,
+        &mut target.a4,
+        &mut target.a5,
+        &mut target.a6,
+    ];
+    let gains = freqs.map(|f| lpc::envelope(&phoneme.ks, f, SAMPLE_RATE));
+    let mut used = amps
+        .into_iter()
+        .zip(gains)
+        .filter(|(a, _)| **a > 0)
+        .collect::<Vec<_>>();
+    let Some(reference) = used
+        .iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(a, gain)| **a as f64 - gain)
+    else {
+        return;
+    };
+    for (a, gain) in &mut used {
+        **a = (reference + *gain).clamp(0.0, MAX_AMPLITUDE).round() as u8;
+    }
+}
+
+/// Shift the amplitudes of a group of phones to follow their levels in the
+/// recording, keeping the mean amplitude of the group the same.
+///
+/// The first amplitude returned by `amps` is the reference, and the others
+/// keep their offset from it.
+fn set_levels<const N: usize>(
+    voice: &mut KlattVoice,
+    levels: &[(Phone, f64)],
+    amps: impl Fn(&mut ParamTarget) -> [&mut u8; N],
+) {
+    let db = |rms: f64| 20.0 * rms.max(1e-9).log10();
+    let levels = levels
+        .iter()
+        .filter_map(|&(phone, rms)| {
+            let target = voice.phone_mut(phone).target.as_mut()?;
+            Some((phone, *amps(target)[0] as f64, db(rms)))
+        })
+        .collect::<Vec<_>>();
+    if levels.is_empty() {
+        return;
+    }
+    let n = levels.len() as f64;
+    let mean_amp = levels.iter().map(|(_, amp, _)| amp).sum::<f64>() / n;
+    let mean_db = levels.iter().map(|(_, _, db)| db).sum::<f64>() / n;
+    for (phone, _, db) in levels {
+        if let Some(target) = voice.phone_mut(phone).target.as_mut() {
+            let reference = *amps(target)[0] as f64;
+            let shift = mean_amp + db - mean_db - reference;
+            for a in amps(target) {
+                if *a > 0 {
+                    *a = (*a as f64 + shift).clamp(0.0, MAX_AMPLITUDE).round() as u8;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Line spectral pairs.
+//!
+//! The LSP frequencies are an alternative representation of an all-pole
+//! filter, found as the roots on the unit circle of the sum and difference
+//! polynomials of the predictor. Interpolating them gives smoother
+//! intermediate spectra than interpolating reflection coefficients, and the
+//! filter stays stable as long as the frequencies stay in order, which linear
+//! interpolation between two ordered sets preserves.
+//!
+//! Only even filter orders are supported: [`from_reflection`] gives `None`
+//! for an odd order, so callers fall back to interpolating the reflection
+//! coefficients.
+
+use std::f64::consts::PI;
+
+use crate::lpc;
+
+/// Resolution of the search for roots.
+const GRID_SIZE: usize = 4096;
+const BISECT_ITERATIONS: usize = 40;
+/// Number of steps in which a [`Blend`] is evaluated, so that its reflection
+/// coefficients are only recomputed every few samples.
+const BLEND_STEPS: f64 = 64.0;
+
+/// Evaluate a symmetric polynomial of even degree on the unit circle.
+///
+/// The value is real once the linear phase is factored out.
+fn eval_symmetric(c: &[f64], omega: f64) -> f64 {
+    let n = (c.len() - 1) as f64 / 2.0;
+    c.iter()
+        .enumerate()
+        .map(|(i, c)| c * ((n - i as f64) * omega).cos())
+        .sum()
+}
+
+/// The roots of a symmetric polynomial on the upper half of the unit circle.
+fn roots(c: &[f64]) -> Vec<f64> {
+    let mut result = vec![];
+    let mut lo = 0.0;
+    let mut f_lo = eval_symmetric(c, lo);
+    for i in 1..=GRID_SIZE {
+        let hi = i as f64 * PI / GRID_SIZE as f64;
+        let f_hi = eval_symmetric(c, hi);
+        if f_lo * f_hi < 0.0 {
+            let (mut a, mut b, mut f_a) = (lo, hi, f_lo);
+            for _ in 0..BISECT_ITERATIONS {
+                let mid = 0.5 * (a + b);
+                let f_mid = eval_symmetric(c, mid);
+                if f_a * f_mid <= 0.0 {
+                    b = mid;
+                } else {
+                    (a, f_a) = (mid, f_mid);
+                }
+            }
+            result.push(0.5 * (a + b));
+        }
+        (lo, f_lo) = (hi, f_hi);
+    }
+    result
+}
+
+/// Convert reflection coefficients to LSP frequencies, in radians.
+///
+/// Returns `None` for an odd order, or if the roots could not all be found,
+/// which can happen if the filter is unstable or very close to it.
+pub fn from_reflection(ks: &[f64]) -> Option<Vec<f64>> {
+    let order = ks.len();
+    if !order.is_multiple_of(2) {
+        return None;
+    }
+    let a = lpc::predictor(ks);
+    let coeff = |i: usize| a.get(i).copied().unwrap_or_default();
+    // The sum polynomial has a root at z = -1 and the difference polynomial
+    // one at z = 1; divide them out.
+    let mut p = vec![0.0; order + 1];
+    let mut q = vec![0.0; order + 1];
+    for i in 0..=order {
+        let sum = coeff(i) + coeff(order + 1 - i);
+        let diff = coeff(i) - coeff(order + 1 - i);
+        p[i] = sum - if i > 0 { p[i - 1] } else { 0.0 };
+        q[i] = diff + if i > 0 { q[i - 1] } else { 0.0 };
+    }
+    let mut freqs = roots(&p);
+    let q_roots = roots(&q);
+    if freqs.len() != order / 2 || q_roots.len() != order / 2 {
+        return None;
+    }
+    freqs.extend(q_roots);
+    freqs.sort_by(f64::total_cmp);
+    Some(freqs)
+}
+
+/// Multiply out a product of quadratic factors with roots at the given
+/// frequencies, and a final linear factor `1 + sign z^-1`.
+fn expand(freqs: impl Iterator<Item = f64>, sign: f64) -> Vec<f64> {
+    let mut c = vec![1.0];
+    for omega in freqs {
+        let b = -2.0 * omega.cos();
+        let mut next = vec![0.0; c.len() + 2];
+        for (i, &x) in c.iter().enumerate() {
+            next[i] += x;
+            next[i + 1] += b * x;
+            next[i + 2] += x;
+        }
+        c = next;
+    }
+    c.push(0.0);
+    for i in (1..c.len()).rev() {
+        c[i] += sign * c[i - 1];
+    }
+    c
+}
+
+/// Convert LSP frequencies back to reflection coefficients.
+pub fn to_reflection(freqs: &[f64]) -> Vec<f64> {
+    let order = freqs.len();
+    debug_assert!(order.is_multiple_of(2), "LSP needs an even order");
+    let p = expand(freqs.iter().copied().step_by(2), 1.0);
+    let q = expand(freqs.iter().copied().skip(1).step_by(2), -1.0);
+    let a = (0..=order).map(|i| 0.5 * (p[i] + q[i])).collect::<Vec<_>>();
+    lpc::reflection(&a)
+}
+
+/// Interpolate linearly between two sets of LSP frequencies.
+pub fn lerp(a: &[f64], b: &[f64], t: f64) -> Vec<f64> {
+    a.iter().zip(b).map(|(a, b)| a + (b - a) * t).collect()
+}
+
+/// True if reflection coefficients describe a stable filter.
+pub fn is_stable(ks: &[f64]) -> bool {
+    ks.iter().all(|k| k.abs() < 1.0)
+}
+
+/// An interpolation between two filters in the LSP domain.
+pub struct Blend {
+    /// The LSP frequencies of both ends, if they could be found.
+    freqs: Option<(Vec<f64>, Vec<f64>)>,
+    /// The last step evaluated, with its coefficients if they were stable.
+    cache: Option<(i32, Option<Vec<f64>>)>,
+}
+
+impl Blend {
+    pub fn new(from: &[f64], to: &[f64]) -> Self {
+        Self {
+            freqs: from_reflection(from).zip(from_reflection(to)),
+            cache: None,
+        }
+    }
+
+    /// The reflection coefficients a fraction `t` of the way between the
+    /// filters, or `None` if the interpolation isn't possible or would be
+    /// unstable.
+    pub fn get(&mut self, t: f64) -> Option<&[f64]> {
+        let (a, b) = self.freqs.as_ref()?;
+        let step = (t.clamp(0.0, 1.0) * BLEND_STEPS).round() as i32;
+        if self.cache.as_ref().is_none_or(|(s, _)| *s != step) {
+            let ks = to_reflection(&lerp(a, b, step as f64 / BLEND_STEPS));
+            self.cache = Some((step, Some(ks).filter(|ks| is_stable(ks))));
+        }
+        self.cache.as_ref()?.1.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::phonemes::PHONEMES;
+
+    /// The spectra of the built-in phonemes, including the ends of glides.
+    fn spectra() -> Vec<&'static [f64]> {
+        (PHONEMES.iter())
+            .flat_map(|(_, ph)| [Some(&ph.ks), ph.glide.as_ref()])
+            .flatten()
+            .map(|ks| &ks[..])
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        for ks in spectra() {
+            let freqs = from_reflection(ks).unwrap();
+            assert!(freqs.is_sorted(), "{ks:?}");
+            let back = to_reflection(&freqs);
+            for (a, b) in ks.iter().zip(&back) {
+                assert!((a - b).abs() < 1e-6, "{ks:?} {back:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn interpolation_is_stable() {
+        let freqs = spectra()
+            .into_iter()
+            .map(|ks| from_reflection(ks).unwrap())
+            .collect::<Vec<_>>();
+        for a in &freqs {
+            for b in &freqs {
+                for i in 1..8 {
+                    let ks = to_reflection(&lerp(a, b, i as f64 / 8.0));
+                    assert!(is_stable(&ks), "{ks:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn odd_order() {
+        assert_eq!(from_reflection(&[0.5, -0.2, 0.1]), None);
+        let mut blend = Blend::new(&[0.5, -0.2, 0.1], &[0.3, 0.1, 0.0]);
+        assert_eq!(blend.get(0.5), None);
+    }
+}
@@ -8,28 +8,56 @@ use crate::{
     synth::{Params, Synth},
 };
 
+mod allophone;
+mod extract;
+mod inline;
 mod klatt;
+mod labels;
 mod lpc;
+mod lsp;
+mod numbers;
 mod phonemes;
 mod phones;
 mod phonet;
+mod pitch;
+mod queue;
+mod resample;
 mod sequence;
+mod sp0256;
+mod spell;
+mod ssml;
 mod synth;
 mod text_to_phoneme;
+mod tms5220;
+mod transition;
+mod utterance;
+mod viseme;
+mod vocoder;
+mod voice;
 
 #[cfg(feature = "rpoly")]
 mod lpc_to_formants;
+#[cfg(feature = "rpoly")]
+mod lpc_to_klatt;
 
 #[derive(Parser, Debug)]
 enum Cmd {
     Clip(Clip),
     Lpc(Lpc),
+    Extract(ExtractCmd),
+    Vocode(VocodeCmd),
+    #[cfg(feature = "rpoly")]
+    Formants(FormantsCmd),
+    Encode(EncodeCmd),
+    Decode(DecodeCmd),
     Synth(SynthCmd),
     Phoneme(PhonemeCmd),
     Say(SayCmd),
     Text(TextCmd),
     Klatt(KlattCmd),
     Phonet(PhonetCmd),
+    Queue(QueueCmd),
+    Voice(VoiceCmd),
 }
 
 #[derive(Parser, Debug)]
@@ -40,18 +68,107 @@ struct Clip {
     out_file: String,
 }
 
+/// LPC analysis of a recording, with pitch detection.
+///
+/// Each frame is printed with its time, F0 (or `-` if unvoiced), reflection
+/// coefficients and RMS. Voiced frames are analyzed with pre-emphasis.
+/// Recordings at other rates are resampled to 16 kHz, and lengths are in
+/// samples at that rate.
 #[derive(Parser, Debug)]
 struct Lpc {
     audio_file: String,
-    start: f64,
-    end: f64,
+    /// Start time in seconds, otherwise the start of the file
+    start: Option<f64>,
+    /// End time in seconds, otherwise the end of the file
+    end: Option<f64>,
     #[arg(short, long)]
     #[clap(default_value = "0.9375")]
     preemph: f64,
     #[arg(short, long)]
     out_file: Option<String>,
+    /// Treat all frames as voiced, rather than detecting voicing
     #[arg(short, long)]
     voiced: bool,
+    #[arg(short, long, value_enum)]
+    #[clap(default_value = "rectangular")]
+    window: lpc::Window,
+    /// Length of the analysis window
+    #[arg(long, value_parser = parse_count)]
+    #[clap(default_value = "800")]
+    window_len: usize,
+    /// Spacing of frames
+    #[arg(long, value_parser = parse_count)]
+    #[clap(default_value = "400")]
+    hop: usize,
+    /// Order of analysis, less than the window length
+    #[arg(long, value_parser = parse_count)]
+    #[clap(default_value_t = lpc::ORDER)]
+    order: usize,
+    #[arg(short, long, value_enum)]
+    #[clap(default_value = "autocorrelation")]
+    method: lpc::Method,
+}
+
+/// Build an LPC voice from a recording with phoneme labels.
+///
+/// The labels may be an Audacity label track or a Praat TextGrid, and the
+/// recording must be at 16 kHz.
+#[derive(Parser, Debug)]
+struct ExtractCmd {
+    audio_file: String,
+    label_file: String,
+    #[arg(short, long)]
+    #[clap(default_value = "0.9375")]
+    preemph: f64,
+    /// Output voice file, binary unless it ends in `.toml`, otherwise the
+    /// voice is printed as TOML
+    #[arg(short, long)]
+    out_file: Option<String>,
+}
+
+/// Resynthesize a recording through LPC analysis and synthesis.
+#[derive(Parser, Debug)]
+struct VocodeCmd {
+    audio_file: String,
+    out_file: String,
+    #[arg(short, long)]
+    #[clap(default_value = "0.9375")]
+    preemph: f64,
+}
+
+/// Track the formants of a recording, writing them as CSV.
+///
+/// Each row has the time, whether the frame is voiced, and the frequency and
+/// bandwidth of F1 to F5 in Hz, empty where a formant wasn't found.
+#[cfg(feature = "rpoly")]
+#[derive(Parser, Debug)]
+struct FormantsCmd {
+    audio_file: String,
+    #[arg(short, long)]
+    #[clap(default_value = "0.9375")]
+    preemph: f64,
+    /// Output file for the track, otherwise it's printed
+    #[arg(short, long)]
+    out_file: Option<String>,
+}
+
+/// Encode a recording as a TMS5220 bitstream.
+///
+/// The recording must be at 8 or 16 kHz.
+#[derive(Parser, Debug)]
+struct EncodeCmd {
+    audio_file: String,
+    out_file: String,
+    #[arg(short, long)]
+    #[clap(default_value = "0.9375")]
+    preemph: f64,
+}
+
+/// Decode a TMS5220 bitstream to an 8 kHz WAV file.
+#[derive(Parser, Debug)]
+struct DecodeCmd {
+    lpc_file: String,
+    out_file: String,
 }
 
 #[derive(Parser, Debug)]
@@ -66,13 +183,54 @@ struct PhonemeCmd {
     //#[arg(short, long)]
     out_file: String,
     phoneme: String,
+    /// LPC voice file to use instead of the built-in voice
+    #[arg(short, long)]
+    voice: Option<String>,
 }
 
+/// Speak text, which may be SSML if it starts with `<`.
+///
+/// Plain text may contain inline escapes, such as `[[ɛksəl]]` for phonemes
+/// or `\pause=300\` for a pause.
 #[derive(Parser, Debug)]
 struct SayCmd {
     //#[arg(short, long)]
     out_file: String,
     text: String,
+    /// Spell the text character by character
+    #[arg(short, long)]
+    spell: bool,
+    /// Read the text as SP0256-AL2 allophones, by code or mnemonic, such
+    /// as `HH1 EH LL AX OW`
+    #[arg(long, conflicts_with = "spell")]
+    sp0256: bool,
+    /// Use the Klatt back end
+    #[arg(short, long)]
+    klatt: bool,
+    /// Print word, phoneme and mark events with their times
+    #[arg(short, long)]
+    events: bool,
+    /// Write a viseme track for lip sync (JSON if the extension is .json,
+    /// otherwise CSV)
+    #[arg(long)]
+    visemes: Option<String>,
+    /// Voice file to use; a Klatt voice selects the Klatt back end
+    #[arg(long)]
+    voice: Option<String>,
+    /// Pitch and volume contour for the LPC back end, with a time in
+    /// seconds, F0 in Hz and optional volume on each line
+    #[arg(long)]
+    contour: Option<String>,
+    /// Vary the pitch of vowels inversely with their F1
+    #[arg(long)]
+    f1_pitch: bool,
+    /// Gate the noise of voiced phonemes with the pitch period, for the LPC
+    /// back end
+    #[arg(long)]
+    modulated_noise: bool,
+    /// Speaking rate, where larger values are faster
+    #[arg(short, long, default_value_t = 1.0, value_parser = parse_rate)]
+    rate: f64,
 }
 
 #[derive(Parser, Debug)]
@@ -80,6 +238,9 @@ struct TextCmd {
     #[arg(short, long)]
     file: bool,
     text: String,
+    /// Spell the text character by character
+    #[arg(short, long)]
+    spell: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -94,6 +255,40 @@ struct PhonetCmd {
     params: String,
 }
 
+/// Speak several messages through the speech queue.
+///
+/// Each message is enqueued at the start, or at a given time if prefixed
+/// with `@ms:`. A message starting with `!` has high priority, and a message
+/// consisting of just `-` stops speech and flushes the queue.
+#[derive(Parser, Debug)]
+struct QueueCmd {
+    out_file: String,
+    messages: Vec<String>,
+    /// LPC voice file to use instead of the built-in voice
+    #[arg(long)]
+    voice: Option<String>,
+}
+
+/// Write a voice file, as TOML if the extension is `.toml`, otherwise in
+/// binary form.
+///
+/// The voice is the built-in one unless an input file is given, so this can
+/// also convert between the two forms.
+#[derive(Parser, Debug)]
+struct VoiceCmd {
+    out_file: String,
+    /// Voice file to convert
+    #[arg(short, long)]
+    input: Option<String>,
+    /// Write the built-in Klatt voice rather than the LPC one
+    #[arg(short, long)]
+    klatt: bool,
+    /// Convert the LPC voice to a Klatt voice, using its formants
+    #[cfg(feature = "rpoly")]
+    #[arg(long)]
+    to_klatt: bool,
+}
+
 fn read_wav(filename: String) -> (hound::WavSpec, Vec<i16>) {
     let mut reader = hound::WavReader::open(&filename).expect("error opening input file");
     let spec = reader.spec();
@@ -108,36 +303,60 @@ fn main_clip(args: Clip) {
     let clip = std::fs::read_to_string(&args.clip_file).expect("error reading clip file");
     for line in clip.lines() {
         let words = line.split_ascii_whitespace().collect::<Vec<_>>();
-        if words.len() >= 2 {
-            if let Ok(start) = words[1].parse::<f64>() {
-                if let Ok(end) = words[2].parse::<f64>() {
-                    let istart = (spec.sample_rate as f64 * start).round() as usize;
-                    let iend = (spec.sample_rate as f64 * end).round() as usize;
-                    for sample in &samples[istart..iend] {
-                        writer.write_sample(*sample).unwrap();
-                    }
-                }
+        if words.len() >= 2
+            && let Ok(start) = words[1].parse::<f64>()
+            && let Ok(end) = words[2].parse::<f64>()
+        {
+            let istart = (spec.sample_rate as f64 * start).round() as usize;
+            let iend = (spec.sample_rate as f64 * end).round() as usize;
+            for sample in &samples[istart..iend] {
+                writer.write_sample(*sample).unwrap();
             }
         }
     }
     writer.finalize().unwrap();
 }
 
+/// Parse a count which must be at least one.
+fn parse_count(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(format!("{e}")),
+    }
+}
+
+/// Parse a speaking rate, which must be positive.
+fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        Ok(_) => Err("must be positive".to_string()),
+        Err(e) => Err(format!("{e}")),
+    }
+}
+
 fn main_lpc(args: Lpc) {
+    const SAMPLE_RATE: u32 = 16_000;
+    if args.order >= args.window_len {
+        input_error(format!(
+            "order must be less than the window length of {}",
+            args.window_len
+        ));
+    }
     let (spec, samples) = read_wav(args.audio_file);
     let samples_f64 = samples.iter().map(|x| *x as f64).collect::<Vec<_>>();
-    let preemph = preemph(&samples_f64, args.preemph);
-    let after_preemph = if args.voiced { &preemph } else { &samples_f64 };
-    let istart = (spec.sample_rate as f64 * args.start).round() as usize;
-    let iend = (spec.sample_rate as f64 * args.end).round() as usize;
-    const FRAME_SIZE: usize = 400;
-    const WINDOW_SIZE: usize = 800;
-    let n_chunks = (iend - istart) / FRAME_SIZE - 1;
+    let samples_f64 = resample::resample(&samples_f64, spec.sample_rate, SAMPLE_RATE);
+    let preemph = lpc::preemph(&samples_f64, args.preemph);
+    let to_sample = |t: f64| ((SAMPLE_RATE as f64 * t).round() as usize).min(samples_f64.len());
+    let istart = args.start.map(to_sample).unwrap_or(0);
+    let iend = args.end.map(to_sample).unwrap_or(samples_f64.len());
+    let n_chunks = (iend.saturating_sub(istart).saturating_sub(args.window_len) / args.hop)
+        + (iend >= istart + args.window_len) as usize;
     let mut out = None;
     if let Some(out_file) = &args.out_file {
         let spec = hound::WavSpec {
             channels: 1,
-            sample_rate: 16_000,
+            sample_rate: SAMPLE_RATE,
             bits_per_sample: 16,
             sample_format: hound::SampleFormat::Int,
         };
@@ -145,21 +364,49 @@ fn main_lpc(args: Lpc) {
     }
     const LEN: usize = 8000;
 
-    for i in 0..n_chunks {
-        let window = &after_preemph[istart + i * FRAME_SIZE..][..WINDOW_SIZE];
-        let coeffs = lpc::Reflector::new(&window);
-        println!("{:.3?} {:.3}", coeffs.ks(), coeffs.rms());
+    let tracker = pitch::PitchTracker::new(SAMPLE_RATE);
+    let windows = (0..n_chunks)
+        .map(|i| istart + i * args.hop..istart + i * args.hop + args.window_len)
+        .collect::<Vec<_>>();
+    let mut periods = windows
+        .iter()
+        .map(|window| {
+            let buf = &samples_f64[window.clone()];
+            let reflector = lpc::Reflector::analyze(buf, args.order, lpc::Method::Autocorrelation);
+            tracker.analyze(buf, &reflector)
+        })
+        .collect::<Vec<_>>();
+    pitch::smooth(&mut periods);
+    for (window, period) in windows.iter().zip(&periods) {
+        let voiced = args.voiced || period.is_some();
+        let buf = if voiced { &preemph } else { &samples_f64 };
+        let windowed = args.window.apply(&buf[window.clone()]);
+        let coeffs = lpc::Reflector::analyze(&windowed, args.order, args.method);
+        let t = window.start as f64 / SAMPLE_RATE as f64;
+        let f0 = match period {
+            Some(p) => format!("{:.1}", tracker.f0(*p)),
+            None => "-".to_string(),
+        };
+        println!("{t:.3} {f0} {:.3?} {:.3}", coeffs.ks(), coeffs.rms());
         #[cfg(feature = "rpoly")]
-        {
-            lpc_to_formants::lpc_to_formants(coeffs.ks());
+        if let Some(formants) = lpc_to_formants::lpc_to_formants(coeffs.ks(), SAMPLE_RATE) {
+            for f in formants {
+                print!(" {:.0}/{:.0}", f.freq, f.bandwidth);
+            }
+            println!();
         }
         if let Some(writer) = &mut out {
-            let period = if args.voiced { 140 } else { 0 };
+            let period = match period {
+                Some(p) => *p as f64,
+                None if voiced => 140.0,
+                None => 0.0,
+            };
             let mut synth = Synth::new(coeffs.ks().len());
             let params = Params {
                 k: coeffs.ks().into(),
                 period,
                 rms: 1.0,
+                noise: 0.0,
             };
             for j in 0..LEN {
                 let y = synth.get_sample(&params);
@@ -173,6 +420,104 @@ fn main_lpc(args: Lpc) {
     }
 }
 
+fn main_extract(args: ExtractCmd) {
+    let (spec, samples) = read_wav(args.audio_file);
+    if spec.sample_rate != extract::SAMPLE_RATE {
+        panic!("sample rate must be {} Hz", extract::SAMPLE_RATE);
+    }
+    let samples_f64 = samples.iter().map(|x| *x as f64).collect::<Vec<_>>();
+    let label_text = std::fs::read_to_string(&args.label_file).expect("error reading label file");
+    let labels = labels::parse(&label_text).expect("invalid label file");
+    let table = extract::extract(&samples_f64, &labels, args.preemph);
+    for name in phonemes::names() {
+        if !table.iter().any(|(n, _)| n == name) {
+            eprintln!("{name}: not labelled");
+        }
+    }
+    let voice = voice::Voice::Lpc(voice::LpcVoice::new(table));
+    match args.out_file {
+        Some(out_file) => write_voice(&out_file, &voice),
+        None => print!("{}", voice.to_toml()),
+    }
+}
+
+fn main_vocode(args: VocodeCmd) {
+    let (spec, samples) = read_wav(args.audio_file);
+    let samples_f64 = samples.iter().map(|x| *x as f64).collect::<Vec<_>>();
+    let frames = vocoder::analyze(
+        &samples_f64,
+        spec.sample_rate,
+        args.preemph,
+        vocoder::HOP,
+        vocoder::WINDOW,
+    );
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: spec.sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(args.out_file, spec).unwrap();
+    for y in vocoder::synthesize(&frames, vocoder::HOP)
+        .into_iter()
+        .take(samples.len())
+    {
+        writer
+            .write_sample(y.clamp(-32768.0, 32767.) as i16)
+            .unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+#[cfg(feature = "rpoly")]
+fn main_formants(args: FormantsCmd) {
+    let (spec, samples) = read_wav(args.audio_file);
+    let samples_f64 = samples.iter().map(|x| *x as f64).collect::<Vec<_>>();
+    let track = lpc_to_formants::track(&samples_f64, spec.sample_rate, args.preemph);
+    let result = match args.out_file {
+        Some(out_file) => std::fs::File::create(out_file)
+            .and_then(|f| lpc_to_formants::write_csv(&mut io::BufWriter::new(f), &track)),
+        None => lpc_to_formants::write_csv(&mut io::stdout().lock(), &track),
+    };
+    result.expect("error writing formant track");
+}
+
+fn main_encode(args: EncodeCmd) {
+    let (spec, samples) = read_wav(args.audio_file);
+    let mut samples_f64 = samples.iter().map(|x| *x as f64).collect::<Vec<_>>();
+    match spec.sample_rate {
+        tms5220::SAMPLE_RATE => (),
+        16_000 => samples_f64 = tms5220::decimate(&samples_f64),
+        _ => panic!("sample rate must be 8 or 16 kHz"),
+    }
+    let data = tms5220::encode(&samples_f64, args.preemph);
+    let secs = samples_f64.len() as f64 / tms5220::SAMPLE_RATE as f64;
+    println!(
+        "{} bytes, {:.0} bit/s",
+        data.len(),
+        data.len() as f64 * 8. / secs
+    );
+    std::fs::write(args.out_file, data).unwrap();
+}
+
+fn main_decode(args: DecodeCmd) {
+    let data = std::fs::read(args.lpc_file).expect("error reading bitstream");
+    let frames = tms5220::decode(&data);
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: tms5220::SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(args.out_file, spec).unwrap();
+    for y in vocoder::synthesize(&frames, tms5220::FRAME_LEN) {
+        writer
+            .write_sample(y.clamp(-32768.0, 32767.) as i16)
+            .unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
 fn simple_env(i: usize, len: usize) -> f64 {
     const FADE: usize = 700;
     if i < FADE {
@@ -186,12 +531,6 @@ fn simple_env(i: usize, len: usize) -> f64 {
     }
 }
 
-fn preemph(inp: &[f64], a: f64) -> Vec<f64> {
-    (0..inp.len())
-        .map(|i| inp[i] - inp.get(i.wrapping_sub(1)).cloned().unwrap_or_default() * a)
-        .collect()
-}
-
 fn main_synth(args: SynthCmd) {
     let spec = hound::WavSpec {
         channels: 1,
@@ -208,8 +547,9 @@ fn main_synth(args: SynthCmd) {
     let mut synth = Synth::new(k.len());
     let params = Params {
         k,
-        period: 140,
+        period: 140.0,
         rms: 1.0,
+        noise: 0.0,
     };
     for _ in 0..16_000 {
         let y = synth.get_sample(&params);
@@ -227,13 +567,31 @@ fn main_phoneme(args: PhonemeCmd) {
         sample_format: hound::SampleFormat::Int,
     };
     let mut writer = hound::WavWriter::create(args.out_file, spec).unwrap();
-    let phoneme = get_phoneme(&args.phoneme).expect("phoneme not found");
+    let voice = args.voice.map(|filename| match load_voice(&filename) {
+        voice::Voice::Lpc(lpc) => lpc,
+        voice::Voice::Klatt(_) => panic!("not an LPC voice"),
+    });
+    let phoneme = match &voice {
+        Some(voice) => voice.get(&args.phoneme),
+        None => get_phoneme(&args.phoneme),
+    };
+    let phoneme = phoneme.expect("phoneme not found");
     let mut synth = Synth::new(phoneme.ks.len());
     let k = phoneme.ks.to_vec();
     println!("{k:?} {}", phoneme.ks.len());
-    let period = if phoneme.voiced { 140 } else { 0 };
+    let period = if phoneme.voiced { 140.0_f64 } else { 0.0 };
     let rms = phoneme.rms * 1e-3;
-    let params = Params { k, period, rms };
+    let noise = if phoneme.voiced {
+        phoneme.noise * rms / period.sqrt()
+    } else {
+        0.0
+    };
+    let params = Params {
+        k,
+        period,
+        rms,
+        noise,
+    };
     for j in 0..16_000 {
         let y = synth.get_sample(&params);
         let env = simple_env(j, 16_000);
@@ -244,27 +602,234 @@ fn main_phoneme(args: PhonemeCmd) {
 }
 
 fn main_say(args: SayCmd) {
+    let ttp = crate::text_to_phoneme::TextToPhoneme::new();
+    let voice = match &args.voice {
+        Some(filename) => load_voice(filename),
+        None if args.klatt => voice::Voice::Klatt(voice::KlattVoice::builtin()),
+        None => voice::Voice::Lpc(voice::LpcVoice::builtin()),
+    };
+    let mut items = vec![];
+    if args.sp0256 {
+        items = sp0256::items(&args.text, |ipa| voice.inherent_duration(ipa))
+            .unwrap_or_else(|e| input_error(format!("invalid allophones: {e}")));
+    } else if args.spell {
+        items.push(utterance::Item::Word {
+            phonemes: spell::spell(&args.text),
+            src: 0..args.text.len(),
+        });
+    } else if args.text.trim_start().starts_with('<') {
+        items = ssml::parse(&ttp, &args.text)
+            .unwrap_or_else(|e| input_error(format!("invalid SSML: {e}")));
+    } else {
+        items = inline::parse(&ttp, &args.text)
+            .unwrap_or_else(|e| input_error(format!("invalid text: {e}")));
+    }
+    let contour = args.contour.map(|filename| {
+        let text = std::fs::read_to_string(filename).expect("error reading contour");
+        sequence::parse_contour(&text).unwrap_or_else(|e| input_error(format!("{e}")))
+    });
+    match voice {
+        voice::Voice::Klatt(_) if contour.is_some() => {
+            panic!("a contour needs the LPC back end");
+        }
+        voice::Voice::Klatt(_) if args.modulated_noise => {
+            panic!("modulated noise needs the LPC back end");
+        }
+        voice::Voice::Klatt(voice) => {
+            let segments = utterance::segments(&items, phones::Phone::parse_ipa);
+            let (events, end) =
+                say_klatt(args.out_file, &voice, segments, args.rate, args.f1_pitch);
+            if args.events {
+                print_events(&events, &args.text, |phone| format!("{phone:?}"));
+            }
+            if let Some(file) = args.visemes {
+                let cues = viseme::track(&events, end, |phone| viseme::Viseme::from_phone(*phone));
+                write_visemes(&file, &cues);
+            }
+        }
+        voice::Voice::Lpc(voice) => {
+            let mut segments = utterance::segments(&items, |s| voice.parse(s));
+            // SP0256 allophones are already chosen.
+            if !args.sp0256 {
+                segments = voice.choose_allophones(segments);
+            }
+            let (events, end) = say_lpc(
+                args.out_file,
+                &voice,
+                segments,
+                contour.unwrap_or_default(),
+                args.rate,
+                args.f1_pitch,
+                args.modulated_noise,
+            );
+            if args.events {
+                print_events(&events, &args.text, |ph| voice.name(ph).to_string());
+            }
+            if let Some(file) = args.visemes {
+                let cues =
+                    viseme::track(&events, end, |ph| viseme::Viseme::from_ipa(voice.name(ph)));
+                write_visemes(&file, &cues);
+            }
+        }
+    }
+}
+
+/// Report invalid input and exit.
+fn input_error(message: String) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1);
+}
+
+fn load_voice(filename: &str) -> voice::Voice {
+    voice::Voice::load(filename).unwrap_or_else(|e| panic!("error loading voice: {e}"))
+}
+
+fn write_voice(filename: &str, voice: &voice::Voice) {
+    if filename.ends_with(".toml") {
+        std::fs::write(filename, voice.to_toml()).unwrap();
+    } else {
+        let bytes =
+            (voice.to_bytes()).unwrap_or_else(|e| input_error(format!("error writing voice: {e}")));
+        std::fs::write(filename, bytes).unwrap();
+    }
+}
+
+fn main_voice(args: VoiceCmd) {
+    let voice = match &args.input {
+        Some(filename) => load_voice(filename),
+        None if args.klatt => voice::Voice::Klatt(voice::KlattVoice::builtin()),
+        None => voice::Voice::Lpc(voice::LpcVoice::builtin()),
+    };
+    #[cfg(feature = "rpoly")]
+    let voice = match voice {
+        voice::Voice::Lpc(lpc) if args.to_klatt => voice::Voice::Klatt(lpc_to_klatt::convert(&lpc)),
+        voice::Voice::Klatt(_) if args.to_klatt => panic!("not an LPC voice"),
+        voice => voice,
+    };
+    write_voice(&args.out_file, &voice);
+}
+
+fn write_visemes(filename: &str, cues: &[viseme::Cue]) {
+    let mut file = io::BufWriter::new(std::fs::File::create(filename).unwrap());
+    if filename.ends_with(".json") {
+        viseme::write_json(&mut file, cues).unwrap();
+    } else {
+        viseme::write_csv(&mut file, cues).unwrap();
+    }
+}
+
+type TimedEvents<P> = Vec<(f64, utterance::Event<P>)>;
+
+/// Render segments with the LPC back end.
+///
+/// Returns timed events and the total duration, in seconds.
+fn say_lpc<'a>(
+    out_file: String,
+    voice: &'a voice::LpcVoice,
+    segments: Vec<utterance::Segment<&'a phonemes::Phoneme>>,
+    contour: Vec<sequence::ContourPoint>,
+    rate: f64,
+    f1_pitch: bool,
+    modulated_noise: bool,
+) -> (TimedEvents<&'a phonemes::Phoneme>, f64) {
     let spec = hound::WavSpec {
         channels: 1,
         sample_rate: 16_000,
         bits_per_sample: 16,
         sample_format: hound::SampleFormat::Int,
     };
-    let mut writer = hound::WavWriter::create(args.out_file, spec).unwrap();
-    let phoneme_seq;
-    if args.text.starts_with('/') {
-        phoneme_seq = crate::phonemes::parse(&args.text[1..]);
-    } else {
-        let ttp = crate::text_to_phoneme::TextToPhoneme::new();
-        let phonemes = ttp.translate(&format!(" {} ", args.text));
-        phoneme_seq = crate::phonemes::parse(&phonemes);
-    }
-    let mut seq = crate::sequence::Sequence::new(phoneme_seq);
-    while let Some(y) = seq.get() {
+    let mut writer = hound::WavWriter::create(out_file, spec).unwrap();
+    let mut seq = crate::sequence::Sequence::new(segments);
+    seq.set_voice(voice);
+    seq.set_rate(rate);
+    seq.set_contour(contour);
+    seq.set_f1_pitch(f1_pitch);
+    seq.set_modulated_noise(modulated_noise);
+    let mut events = vec![];
+    let mut i = 0;
+    loop {
+        let y = seq.get();
+        while let Some(event) = seq.next_event() {
+            events.push((i as f64 / 16_000., event));
+        }
+        let Some(y) = y else {
+            break;
+        };
         let yi = (y * 16384.).clamp(-32768.0, 32767.) as i16;
         writer.write_sample(yi).unwrap();
+        i += 1;
     }
     writer.finalize().unwrap();
+    (events, i as f64 / 16_000.)
+}
+
+/// Render segments with the Klatt back end.
+///
+/// Returns timed events and the total duration, in seconds.
+fn say_klatt(
+    out_file: String,
+    voice: &voice::KlattVoice,
+    segments: Vec<utterance::Segment<phones::Phone>>,
+    rate: f64,
+    f1_pitch: bool,
+) -> (TimedEvents<phones::Phone>, f64) {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 10_000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(out_file, spec).unwrap();
+    let mut phonet = phonet::Phonet::new(voice);
+    phonet.set_rate(rate);
+    phonet.set_f1_pitch(f1_pitch);
+    let mut segments = segments.into_iter();
+    let mut klatt = crate::klatt::Klatt::default();
+    let mut klatt_params = KlattParams::default();
+    let mut events = vec![];
+    let mut i = 0;
+    loop {
+        while phonet.inp_ready()
+            && let Some(segment) = segments.next()
+        {
+            phonet.push_segment(segment);
+        }
+        while let Some(event) = phonet.next_event() {
+            events.push((i as f64 / 10_000., event));
+        }
+        if phonet.inp_ready() {
+            break;
+        }
+        phonet.get_frame(&mut klatt_params);
+        klatt.set(&klatt_params);
+        for _ in 0..50 {
+            let y = klatt.process();
+            let yi = (y * 16384.).clamp(-32768.0, 32767.) as i16;
+            writer.write_sample(yi).unwrap();
+            i += 1;
+        }
+    }
+    writer.finalize().unwrap();
+    (events, i as f64 / 10_000.)
+}
+
+fn print_events<P>(
+    events: &[(f64, utterance::Event<P>)],
+    text: &str,
+    phone_name: impl Fn(&P) -> String,
+) {
+    for (t, event) in events {
+        match event {
+            utterance::Event::WordStart(src) => {
+                println!("{t:.3} word start {src:?} {:?}", &text[src.clone()])
+            }
+            utterance::Event::WordEnd(src) => {
+                println!("{t:.3} word end {src:?} {:?}", &text[src.clone()])
+            }
+            utterance::Event::Phone(phone) => println!("{t:.3} phone {}", phone_name(phone)),
+            utterance::Event::Mark(name) => println!("{t:.3} mark {name}"),
+        }
+    }
 }
 
 fn main_text(args: TextCmd) {
@@ -274,8 +839,14 @@ fn main_text(args: TextCmd) {
         let reader = io::BufReader::new(file);
         for word in reader.lines() {
             let w = word.unwrap();
-            println!("{w}: {}", ttp.translate(&format!(" {w} ")));
+            if args.spell {
+                println!("{w}: {}", spell::spell(&w));
+            } else {
+                println!("{w}: {}", ttp.translate(&format!(" {w} ")));
+            }
         }
+    } else if args.spell {
+        println!("{}", spell::spell(&args.text));
     } else {
         println!("{}", ttp.translate(&format!(" {} ", args.text)));
     }
@@ -339,7 +910,8 @@ fn main_phonet(args: PhonetCmd) {
         sample_format: hound::SampleFormat::Int,
     };
     let mut writer = hound::WavWriter::create(args.out_file, spec).unwrap();
-    let mut phonet = phonet::Phonet::default();
+    let voice = voice::KlattVoice::builtin();
+    let mut phonet = phonet::Phonet::new(&voice);
     let mut phones = args.params.split(' ');
     let mut klatt = crate::klatt::Klatt::default();
 
@@ -366,17 +938,109 @@ fn main_phonet(args: PhonetCmd) {
     writer.finalize().unwrap();
 }
 
+fn main_queue(args: QueueCmd) {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 16_000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(args.out_file, spec).unwrap();
+    let ttp = crate::text_to_phoneme::TextToPhoneme::new();
+    let voice = match &args.voice {
+        Some(filename) => match load_voice(filename) {
+            voice::Voice::Lpc(lpc) => lpc,
+            voice::Voice::Klatt(_) => input_error("the queue needs an LPC voice".to_string()),
+        },
+        None => voice::LpcVoice::builtin(),
+    };
+    let mut pending = args
+        .messages
+        .iter()
+        .map(|msg| {
+            if let Some(rest) = msg.strip_prefix('@')
+                && let Some((ms, text)) = rest.split_once(':')
+            {
+                let time = ms.parse::<usize>().expect("invalid time") * 16;
+                (time, text)
+            } else {
+                (0, msg.as_str())
+            }
+        })
+        .collect::<Vec<_>>();
+    pending.sort_by_key(|(time, _)| *time);
+    let mut pending = pending.into_iter().peekable();
+    let mut queue = queue::SpeechQueue::new(&voice);
+    let (tx, rx) = std::sync::mpsc::channel();
+    queue.set_callback(move |event| tx.send(event).unwrap());
+    let mut i = 0;
+    loop {
+        while let Some((_, text)) = pending.next_if(|(time, _)| *time <= i) {
+            if text == "-" {
+                queue.stop();
+                continue;
+            }
+            let (text, priority) = match text.strip_prefix('!') {
+                Some(text) => (text, queue::Priority::High),
+                None => (text, queue::Priority::Normal),
+            };
+            let items = match inline::parse(&ttp, text) {
+                Ok(items) => items,
+                Err(e) => {
+                    eprintln!("invalid text {text:?}: {e}");
+                    continue;
+                }
+            };
+            let segments = utterance::segments(&items, |s| voice.parse(s));
+            let segments = voice.choose_allophones(segments);
+            let index = queue.enqueue(segments, priority);
+            let t = i as f64 / 16_000.;
+            println!("{t:.3}: enqueue {index} {priority:?} {text:?}");
+        }
+        if queue.is_idle() && pending.peek().is_none() {
+            break;
+        }
+        let y = queue.get().unwrap_or_default();
+        for event in rx.try_iter() {
+            let t = i as f64 / 16_000.;
+            match event {
+                queue::Event::Start(index) => println!("{t:.3}: start {index}"),
+                queue::Event::Progress { index, pos, len } => {
+                    println!("{t:.3}: progress {index} {pos}/{len}")
+                }
+                queue::Event::Word { index, src } => println!("{t:.3}: word {index} {src:?}"),
+                queue::Event::Mark { index, name } => println!("{t:.3}: mark {index} {name}"),
+                queue::Event::End(index) => println!("{t:.3}: end {index}"),
+                queue::Event::Preempted(index) => println!("{t:.3}: preempted {index}"),
+                queue::Event::Cancelled(index) => println!("{t:.3}: cancelled {index}"),
+            }
+        }
+        let yi = (y * 16384.).clamp(-32768.0, 32767.) as i16;
+        writer.write_sample(yi).unwrap();
+        i += 1;
+    }
+    writer.finalize().unwrap();
+}
+
 fn main() {
     let cmd = Cmd::parse();
     //println!("{cmd:?}");
     match cmd {
         Cmd::Clip(args) => main_clip(args),
         Cmd::Lpc(lpc) => main_lpc(lpc),
+        Cmd::Extract(extract) => main_extract(extract),
+        Cmd::Vocode(vocode) => main_vocode(vocode),
+        #[cfg(feature = "rpoly")]
+        Cmd::Formants(formants) => main_formants(formants),
+        Cmd::Encode(encode) => main_encode(encode),
+        Cmd::Decode(decode) => main_decode(decode),
         Cmd::Synth(synth) => main_synth(synth),
         Cmd::Phoneme(phoneme) => main_phoneme(phoneme),
         Cmd::Say(seq) => main_say(seq),
         Cmd::Text(text) => main_text(text),
         Cmd::Klatt(klatt) => main_klatt(klatt),
         Cmd::Phonet(phonet) => main_phonet(phonet),
+        Cmd::Queue(queue) => main_queue(queue),
+        Cmd::Voice(voice) => main_voice(voice),
     }
 }
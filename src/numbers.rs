@@ -0,0 +1,154 @@
+//! Expansion of numbers into words.
+//!
+//! The output is lowercase English text, suitable for [`TextToPhoneme`].
+//!
+//! [`TextToPhoneme`]: crate::text_to_phoneme::TextToPhoneme
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+const SCALES: [(u64, &str); 6] = [
+    (1_000_000_000_000_000_000, "quintillion"),
+    (1_000_000_000_000_000, "quadrillion"),
+    (1_000_000_000_000, "trillion"),
+    (1_000_000_000, "billion"),
+    (1_000_000, "million"),
+    (1_000, "thousand"),
+];
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// A cardinal number, for example "one hundred twenty three".
+pub fn cardinal(n: u64) -> String {
+    let mut result = String::new();
+    push_cardinal(n, &mut result);
+    result
+}
+
+/// An ordinal number, for example "twenty third".
+pub fn ordinal(n: u64) -> String {
+    let mut result = cardinal(n);
+    let last_start = result.rfind(' ').map(|i| i + 1).unwrap_or(0);
+    let last = &result[last_start..];
+    let replacement = match last {
+        "one" => "first",
+        "two" => "second",
+        "three" => "third",
+        "five" => "fifth",
+        "eight" => "eighth",
+        "nine" => "ninth",
+        "twelve" => "twelfth",
+        _ => "",
+    };
+    if !replacement.is_empty() {
+        result.replace_range(last_start.., replacement);
+    } else if let Some(stem) = last.strip_suffix('y') {
+        let word = format!("{stem}ieth");
+        result.replace_range(last_start.., &word);
+    } else {
+        result.push_str("th");
+    }
+    result
+}
+
+/// A year, read in the usual way, for example "nineteen eighty four".
+pub fn year(n: u64) -> String {
+    if (1100..10_000).contains(&n) && n % 1000 >= 100 {
+        let (hi, lo) = (n / 100, n % 100);
+        let mut result = cardinal(hi);
+        result.push(' ');
+        match lo {
+            0 => result.push_str("hundred"),
+            1..10 => {
+                result.push_str("oh ");
+                push_cardinal(lo, &mut result);
+            }
+            _ => push_cardinal(lo, &mut result),
+        }
+        result
+    } else {
+        cardinal(n)
+    }
+}
+
+/// The name of a month, numbered from 1.
+pub fn month(n: u64) -> Option<&'static str> {
+    MONTHS.get((n as usize).wrapping_sub(1)).copied()
+}
+
+fn push_cardinal(mut n: u64, result: &mut String) {
+    if n == 0 {
+        result.push_str(ONES[0]);
+        return;
+    }
+    for (scale, name) in SCALES {
+        if n >= scale {
+            push_below_thousand(n / scale, result);
+            push_word(name, result);
+            n %= scale;
+        }
+    }
+    if n > 0 {
+        push_below_thousand(n, result);
+    }
+}
+
+fn push_below_thousand(n: u64, result: &mut String) {
+    let (hundreds, rest) = (n / 100, n % 100);
+    if hundreds > 0 {
+        push_word(ONES[hundreds as usize], result);
+        push_word("hundred", result);
+    }
+    if rest >= 20 {
+        push_word(TENS[rest as usize / 10], result);
+        if rest % 10 > 0 {
+            push_word(ONES[rest as usize % 10], result);
+        }
+    } else if rest > 0 {
+        push_word(ONES[rest as usize], result);
+    }
+}
+
+fn push_word(word: &str, result: &mut String) {
+    if !result.is_empty() && !result.ends_with(' ') {
+        result.push(' ');
+    }
+    result.push_str(word);
+}
@@ -1,10 +1,36 @@
 use std::borrow::Cow;
 
+use serde::{Deserialize, Serialize};
+
+use crate::phones::PhoneDuration;
+
+#[derive(Clone)]
 pub struct Phoneme {
     pub kind: Kind,
     pub voiced: bool,
     pub ks: Cow<'static, [f64]>,
     pub rms: f64,
+    /// RMS of the noise mixed into voicing, relative to that of the voicing.
+    pub noise: f64,
+    /// Minimum and inherent duration in ms, or the default for the kind.
+    pub duration: Option<PhoneDuration>,
+    /// Reflection coefficients at the end of a diphthong, which glides to
+    /// them from `ks`.
+    pub glide: Option<Cow<'static, [f64]>>,
+    /// The release of a plosive after its closure, or `None` to play it as a
+    /// short burst like other phonemes.
+    pub stop: Option<Stop>,
+}
+
+/// The release of a plosive, which follows a closure lasting the rest of its
+/// duration. The closure is silent, or a voice bar if voiced. Times are in ms.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Stop {
+    /// Noise through `ks` as the closure opens.
+    pub burst: f64,
+    /// Aspiration, or voicing if voiced, moving from `ks` towards the
+    /// spectrum of the next phoneme.
+    pub release: f64,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -16,7 +42,14 @@ pub enum Kind {
     Affricate,
 }
 
-const UNVOICED_MUL: f64 = 0.1;
+/// Scale of the RMS of fricatives relative to the analysis.
+pub const UNVOICED_MUL: f64 = 0.1;
+
+/// Noise of voiced fricatives and affricates, relative to their voicing.
+const VOICED_FRICATION: f64 = 1.0;
+
+/// Noise of voiced plosive bursts, relative to their voicing.
+const VOICED_BURST: f64 = 0.5;
 
 impl Phoneme {
     const fn vowel(ks: &'static [f64], rms: f64) -> Self {
@@ -25,6 +58,10 @@ impl Phoneme {
             voiced: true,
             ks: Cow::Borrowed(ks),
             rms,
+            noise: 0.0,
+            duration: None,
+            glide: None,
+            stop: None,
         }
     }
 
@@ -34,6 +71,10 @@ impl Phoneme {
             voiced: false,
             ks: Cow::Borrowed(ks),
             rms: rms * UNVOICED_MUL,
+            noise: 0.0,
+            duration: None,
+            glide: None,
+            stop: None,
         }
     }
 
@@ -43,6 +84,10 @@ impl Phoneme {
             voiced: true,
             ks: Cow::Borrowed(ks),
             rms: rms * UNVOICED_MUL,
+            noise: VOICED_FRICATION,
+            duration: None,
+            glide: None,
+            stop: None,
         }
     }
 
@@ -52,6 +97,10 @@ impl Phoneme {
             voiced: true,
             ks: Cow::Borrowed(ks),
             rms,
+            noise: 0.0,
+            duration: None,
+            glide: None,
+            stop: None,
         }
     }
 
@@ -61,6 +110,10 @@ impl Phoneme {
             voiced,
             ks: Cow::Borrowed(ks),
             rms,
+            noise: if voiced { VOICED_BURST } else { 0.0 },
+            duration: None,
+            glide: None,
+            stop: None,
         }
     }
 
@@ -70,41 +123,94 @@ impl Phoneme {
             voiced,
             ks: Cow::Borrowed(ks),
             rms,
+            noise: if voiced { VOICED_FRICATION } else { 0.0 },
+            duration: None,
+            glide: None,
+            stop: None,
+        }
+    }
+
+    const fn lasting(mut self, min: u16, inherent: u16) -> Self {
+        self.duration = Some(PhoneDuration {
+            min_duration: min,
+            inherent_duration: inherent,
+        });
+        self
+    }
+
+    const fn released(mut self, burst: f64, release: f64) -> Self {
+        self.stop = Some(Stop { burst, release });
+        self
+    }
+
+    const fn diphthong(start: &'static [f64], end: &'static [f64], rms: f64) -> Self {
+        Self {
+            kind: Kind::Vowel,
+            voiced: true,
+            ks: Cow::Borrowed(start),
+            rms,
+            noise: 0.0,
+            duration: None,
+            glide: Some(Cow::Borrowed(end)),
+            stop: None,
         }
     }
 }
 
-const PHONEMES: &[(&'static str, Phoneme)] = &[
-    (
-        "ʊ",
-        Phoneme::vowel(
-            &[
-                -0.748, 0.401, -0.448, -0.010, 0.112, 0.193, -0.147, 0.616, -0.303, -0.050, 0.015,
-                0.001, -0.205, 0.215, 0.263, 0.494, 0.208, 0.087,
-            ],
-            223.,
-        ),
-    ),
-    (
-        "ʊ2", // this is end of oʊ glide
-        Phoneme::vowel(
-            &[
-                -0.783, 0.278, -0.428, 0.105, 0.207, 0.119, -0.012, 0.688, -0.433, 0.038, -0.045,
-                -0.168, -0.056, 0.211, 0.097, 0.417, 0.392, 0.066,
-            ],
-            164.,
-        ),
-    ),
-    (
-        "ɛ",
-        Phoneme::vowel(
-            &[
-                -0.631, 0.546, -0.323, 0.357, 0.043, -0.003, -0.382, 0.376, -0.259, 0.130, -0.215,
-                0.181, 0.066, 0.273, 0.385, 0.164, 0.131, -0.054,
-            ],
-            356.,
-        ),
-    ),
+// Spectra shared by monophthongs and diphthongs.
+const KS_A: &[f64] = &[
+    -0.743, 0.603, -0.395, 0.317, -0.053, 0.226, -0.104, 0.373, -0.228, 0.199, 0.043, -0.082,
+    0.002, 0.203, 0.263, 0.183, 0.295, 0.092,
+];
+const KS_EH: &[f64] = &[
+    -0.631, 0.546, -0.323, 0.357, 0.043, -0.003, -0.382, 0.376, -0.259, 0.130, -0.215, 0.181,
+    0.066, 0.273, 0.385, 0.164, 0.131, -0.054,
+];
+const KS_E: &[f64] = &[
+    -0.409, 0.437, 0.039, 0.014, -0.056, 0.166, -0.340, -0.386, 0.036, 0.023, -0.035, 0.182, 0.115,
+    0.315, 0.274, 0.302, -0.062, 0.185,
+];
+const KS_IH: &[f64] = &[
+    -0.449, 0.528, -0.035, 0.110, -0.074, 0.198, -0.497, 0.000, 0.058, -0.250, -0.050, 0.125,
+    0.003, 0.070, 0.313, 0.357, -0.084, 0.207,
+];
+const KS_AO: &[f64] = &[
+    -0.875, 0.341, -0.227, 0.257, 0.085, 0.220, -0.230, 0.235, 0.042, 0.066, -0.187, 0.022, 0.085,
+    -0.136, -0.118, 0.286, 0.307, 0.279,
+];
+const KS_O: &[f64] = &[
+    -0.896, 0.414, -0.449, 0.042, 0.166, 0.286, 0.189, 0.690, -0.382, -0.272, 0.037, -0.312,
+    -0.089, 0.196, 0.150, 0.302, 0.299, -0.027,
+];
+const KS_UH: &[f64] = &[
+    -0.748, 0.401, -0.448, -0.010, 0.112, 0.193, -0.147, 0.616, -0.303, -0.050, 0.015, 0.001,
+    -0.205, 0.215, 0.263, 0.494, 0.208, 0.087,
+];
+/// The end of an oʊ glide.
+const KS_UH2: &[f64] = &[
+    -0.783, 0.278, -0.428, 0.105, 0.207, 0.119, -0.012, 0.688, -0.433, 0.038, -0.045, -0.168,
+    -0.056, 0.211, 0.097, 0.417, 0.392, 0.066,
+];
+
+// Burst spectra of the voiceless plosives, shared with their allophones.
+const KS_T: &[f64] = &[
+    0.270, 0.510, 0.195, -0.086, -0.390, -0.021, -0.230, 0.040, -0.120, 0.091, -0.091, 0.138,
+    -0.038, 0.169, 0.077, 0.191, -0.080, 0.059,
+];
+const KS_K: &[f64] = &[
+    -0.885, 0.515, 0.272, 0.163, 0.165, -0.144, -0.669, 0.130, 0.005, 0.186, -0.109, 0.083, -0.037,
+    -0.009, 0.047, -0.007, 0.058, -0.099,
+];
+const KS_P: &[f64] = &[
+    -0.922, 0.226, -0.103, 0.074, -0.196, 0.195, 0.094, 0.275, -0.189, 0.053, -0.004, -0.028,
+    0.065, 0.229, -0.081, 0.099, 0.032, 0.039,
+];
+
+/// The built-in phonemes, with durations mostly from table 9-1 of the book.
+/// The durations of plosives include their closure and burst.
+pub const PHONEMES: &[(&str, Phoneme)] = &[
+    ("ʊ", Phoneme::vowel(KS_UH, 223.).lasting(60, 160)),
+    ("ɛ", Phoneme::vowel(KS_EH, 356.).lasting(70, 150)),
     (
         "u",
         Phoneme::vowel(
@@ -113,18 +219,10 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.222, 0.096, 0.005, -0.006, 0.671, 0.135, -0.003,
             ],
             241.,
-        ),
-    ),
-    (
-        "ɪ",
-        Phoneme::vowel(
-            &[
-                -0.449, 0.528, -0.035, 0.110, -0.074, 0.198, -0.497, 0.000, 0.058, -0.250, -0.050,
-                0.125, 0.003, 0.070, 0.313, 0.357, -0.084, 0.207,
-            ],
-            348.,
-        ),
+        )
+        .lasting(70, 210),
     ),
+    ("ɪ", Phoneme::vowel(KS_IH, 348.).lasting(40, 135)),
     (
         "æ",
         Phoneme::vowel(
@@ -133,7 +231,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 -0.171, 0.194, 0.260, 0.109, 0.372, -0.031, -0.046,
             ],
             404.,
-        ),
+        )
+        .lasting(80, 230),
     ),
     (
         "ɑ",
@@ -143,7 +242,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.014, -0.046, 0.289, 0.261, 0.173, 0.084, 0.058,
             ],
             374.,
-        ),
+        )
+        .lasting(100, 240),
     ),
     (
         "ʌ",
@@ -153,17 +253,12 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.051, 0.051, 0.259, 0.287, 0.298, 0.083, -0.058,
             ],
             356.,
-        ),
+        )
+        .lasting(60, 140),
     ),
     (
         "ɔ", // not a great capture
-        Phoneme::vowel(
-            &[
-                -0.875, 0.341, -0.227, 0.257, 0.085, 0.220, -0.230, 0.235, 0.042, 0.066, -0.187,
-                0.022, 0.085, -0.136, -0.118, 0.286, 0.307, 0.279,
-            ],
-            200.,
-        ),
+        Phoneme::vowel(KS_AO, 200.).lasting(100, 240),
     ),
     (
         "i",
@@ -173,18 +268,10 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.304, 0.161, 0.118, -0.117, -0.074, -0.399, 0.055,
             ],
             358.,
-        ),
-    ),
-    (
-        "o",
-        Phoneme::vowel(
-            &[
-                -0.896, 0.414, -0.449, 0.042, 0.166, 0.286, 0.189, 0.690, -0.382, -0.272, 0.037,
-                -0.312, -0.089, 0.196, 0.150, 0.302, 0.299, -0.027,
-            ],
-            100.,
-        ),
+        )
+        .lasting(55, 155),
     ),
+    ("o", Phoneme::vowel(KS_O, 100.).lasting(80, 220)),
     (
         "ə",
         Phoneme::vowel(
@@ -193,27 +280,39 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.213, 0.044, 0.109, 0.445, 0.137, 0.156, -0.012,
             ],
             220.,
-        ),
+        )
+        .lasting(60, 120),
     ),
+    ("e", Phoneme::vowel(KS_E, 344.).lasting(100, 190)),
+    ("a", Phoneme::vowel(KS_A, 350.).lasting(100, 240)),
+    // Diphthongs, with the spectra of their start and end vowels.
     (
-        "e",
-        Phoneme::vowel(
-            &[
-                -0.409, 0.437, 0.039, 0.014, -0.056, 0.166, -0.340, -0.386, 0.036, 0.023, -0.035,
-                0.182, 0.115, 0.315, 0.274, 0.302, -0.062, 0.185,
-            ],
-            344.,
-        ),
+        "aɪ",
+        Phoneme::diphthong(KS_A, KS_IH, 350.).lasting(150, 250),
     ),
     (
-        "a",
-        Phoneme::vowel(
-            &[
-                -0.743, 0.603, -0.395, 0.317, -0.053, 0.226, -0.104, 0.373, -0.228, 0.199, 0.043,
-                -0.082, 0.002, 0.203, 0.263, 0.183, 0.295, 0.092,
-            ],
-            350.,
-        ),
+        "aʊ",
+        Phoneme::diphthong(KS_A, KS_UH, 350.).lasting(100, 260),
+    ),
+    (
+        "aw",
+        Phoneme::diphthong(KS_A, KS_UH, 350.).lasting(100, 260),
+    ),
+    (
+        "ɛɪ",
+        Phoneme::diphthong(KS_EH, KS_IH, 356.).lasting(100, 190),
+    ),
+    (
+        "eɪ",
+        Phoneme::diphthong(KS_E, KS_IH, 344.).lasting(100, 190),
+    ),
+    (
+        "ɔɪ",
+        Phoneme::diphthong(KS_AO, KS_IH, 200.).lasting(150, 280),
+    ),
+    (
+        "oʊ",
+        Phoneme::diphthong(KS_O, KS_UH2, 100.).lasting(80, 220),
     ),
     (
         "ɚ", // yeah this isn't a vowel
@@ -223,7 +322,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 -0.016, 0.004, -0.064, 0.067, 0.212, -0.096, -0.206,
             ],
             150.,
-        ),
+        )
+        .lasting(80, 180),
     ),
     (
         "ɹ", // yeah this isn't a vowel
@@ -233,7 +333,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.110, -0.033, 0.134, 0.329, 0.151, -0.001, 0.166,
             ],
             200.,
-        ),
+        )
+        .lasting(30, 80),
     ),
     (
         "l", // this isn't a vowel either
@@ -243,7 +344,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.299, 0.236, 0.234, -0.197, -0.301, -0.229, -0.173,
             ],
             131.,
-        ),
+        )
+        .lasting(40, 80),
     ),
     (
         "w", // this is more vowel-like
@@ -253,7 +355,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.126, -0.014, 0.174, 0.196, -0.065, -0.194, -0.004,
             ],
             144.,
-        ),
+        )
+        .lasting(60, 80),
     ),
     (
         "ʍ", // just a copy of w for now
@@ -263,7 +366,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.126, -0.014, 0.174, 0.196, -0.065, -0.194, -0.004,
             ],
             144.,
-        ),
+        )
+        .lasting(60, 70),
     ),
     (
         "j",
@@ -273,7 +377,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.236, -0.084, -0.033, -0.048, 0.157, -0.222, -0.025,
             ],
             204.,
-        ),
+        )
+        .lasting(40, 80),
     ),
     (
         "s",
@@ -283,7 +388,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.155, -0.059, 0.158, 0.021, 0.235, 0.063, 0.134,
             ],
             466.,
-        ),
+        )
+        .lasting(60, 105),
     ),
     (
         "z",
@@ -293,7 +399,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.002, -0.090, 0.077, -0.040, 0.112, -0.150, 0.077,
             ],
             1600., // spammed much higher than analysis
-        ),
+        )
+        .lasting(40, 75),
     ),
     (
         "f",
@@ -303,7 +410,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 -0.088, -0.132, 0.020, -0.092, 0.136, -0.033, 0.007,
             ],
             101.,
-        ),
+        )
+        .lasting(80, 100),
     ),
     (
         "v",
@@ -313,7 +421,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 -0.033, -0.124, -0.080, -0.098, 0.259, 0.205, 0.285,
             ],
             500.,
-        ),
+        )
+        .lasting(40, 60),
     ),
     (
         "θ",
@@ -323,7 +432,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.106, 0.081, 0.089, 0.007, -0.029, -0.073, -0.050,
             ],
             100.,
-        ),
+        )
+        .lasting(60, 90),
     ),
     (
         "ð",
@@ -333,7 +443,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 -0.030, 0.099, 0.044, 0.095, 0.050, 0.298, -0.026, -0.063,
             ],
             600., // boosted
-        ),
+        )
+        .lasting(30, 50),
     ),
     (
         "ʃ",
@@ -343,7 +454,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 -0.127, -0.190, -0.010, -0.119, 0.002, 0.036, 0.225,
             ],
             498.,
-        ),
+        )
+        .lasting(80, 105),
     ),
     (
         "ʒ", // didn't sample
@@ -353,7 +465,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 -0.047, -0.194, -0.045, -0.174, -0.005, -0.036, 0.162,
             ],
             2000.,
-        ),
+        )
+        .lasting(40, 70),
     ),
     (
         "h",
@@ -363,7 +476,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 -0.010, -0.054, -0.138, 0.172, 0.061, -0.224, 0.074,
             ],
             59.,
-        ),
+        )
+        .lasting(20, 80),
     ),
     (
         "m",
@@ -373,7 +487,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.180, 0.118, 0.004, 0.090, 0.183, 0.048, -0.092,
             ],
             100.,
-        ),
+        )
+        .lasting(60, 70),
     ),
     (
         "n",
@@ -383,7 +498,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 -0.012, 0.039, 0.092, -0.001, -0.038, -0.040, 0.039,
             ],
             131.,
-        ),
+        )
+        .lasting(50, 60),
     ),
     (
         "ŋ",
@@ -393,40 +509,27 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.176, -0.084, -0.097, 0.058, 0.314, -0.060, -0.103,
             ],
             200.,
-        ),
+        )
+        .lasting(60, 95),
     ),
+    // Unaspirated, as after s, with a short release.
     (
         "t",
-        Phoneme::plosive(
-            false,
-            &[
-                0.270, 0.510, 0.195, -0.086, -0.390, -0.021, -0.230, 0.040, -0.120, 0.091, -0.091,
-                0.138, -0.038, 0.169, 0.077, 0.191, -0.080, 0.059,
-            ],
-            100., // toned down
-        ),
+        Phoneme::plosive(false, KS_T, 100.) // toned down
+            .lasting(50, 75)
+            .released(10., 15.),
     ),
     (
-        "d", // same LPC as "t", may want to resample
-        Phoneme::plosive(
-            true,
-            &[
-                0.270, 0.510, 0.195, -0.086, -0.390, -0.021, -0.230, 0.040, -0.120, 0.091, -0.091,
-                0.138, -0.038, 0.169, 0.077, 0.191, -0.080, 0.059,
-            ],
-            360.,
-        ),
+        "d",
+        Phoneme::plosive(true, KS_T, 60.)
+            .lasting(50, 75)
+            .released(5., 10.),
     ),
     (
         "k",
-        Phoneme::plosive(
-            false,
-            &[
-                -0.885, 0.515, 0.272, 0.163, 0.165, -0.144, -0.669, 0.130, 0.005, 0.186, -0.109,
-                0.083, -0.037, -0.009, 0.047, -0.007, 0.058, -0.099,
-            ],
-            80.,
-        ),
+        Phoneme::plosive(false, KS_K, 80.)
+            .lasting(60, 80)
+            .released(15., 20.),
     ),
     (
         "g",
@@ -436,19 +539,16 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.049, 0.090, -0.075, 0.080, 0.158, 0.536, 0.013, -0.007, -0.263, -0.052, -0.210,
                 -0.020, -0.111, 0.037, -0.029, 0.076, -0.034, -0.006,
             ],
-            160.,
-        ),
+            50.,
+        )
+        .lasting(60, 80)
+        .released(10., 15.),
     ),
     (
         "p",
-        Phoneme::plosive(
-            false,
-            &[
-                -0.922, 0.226, -0.103, 0.074, -0.196, 0.195, 0.094, 0.275, -0.189, 0.053, -0.004,
-                -0.028, 0.065, 0.229, -0.081, 0.099, 0.032, 0.039,
-            ],
-            100.,
-        ),
+        Phoneme::plosive(false, KS_P, 100.)
+            .lasting(50, 90)
+            .released(5., 15.),
     ),
     (
         "b",
@@ -458,9 +558,58 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 -0.314, 0.028, -0.145, 0.077, -0.205, -0.010, -0.552, -0.001, 0.080, -0.025,
                 -0.074, 0.007, 0.029, 0.130, 0.109, -0.065, -0.081, 0.066,
             ],
-            100.,
-        ),
+            60.,
+        )
+        .lasting(60, 85)
+        .released(5., 10.),
+    ),
+    // Allophones, chosen by the rules in [`crate::allophone`]. Aspiration is
+    // a longer release, unreleased stops end in a faint, short burst, and a
+    // flap is a short voiced closure. Dark l is approximated by ʊ, as in
+    // l-vocalization.
+    (
+        "tʰ",
+        Phoneme::plosive(false, KS_T, 100.)
+            .lasting(50, 75)
+            .released(10., 50.),
+    ),
+    (
+        "kʰ",
+        Phoneme::plosive(false, KS_K, 80.)
+            .lasting(60, 80)
+            .released(15., 55.),
+    ),
+    (
+        "pʰ",
+        Phoneme::plosive(false, KS_P, 100.)
+            .lasting(50, 90)
+            .released(5., 50.),
+    ),
+    (
+        "t̚",
+        Phoneme::plosive(false, KS_T, 33.)
+            .lasting(50, 75)
+            .released(3., 5.),
+    ),
+    (
+        "k̚",
+        Phoneme::plosive(false, KS_K, 26.)
+            .lasting(60, 80)
+            .released(3., 5.),
+    ),
+    (
+        "p̚",
+        Phoneme::plosive(false, KS_P, 33.)
+            .lasting(50, 90)
+            .released(3., 5.),
+    ),
+    (
+        "ɾ",
+        Phoneme::plosive(true, KS_T, 60.)
+            .lasting(20, 20)
+            .released(0., 5.),
     ),
+    ("ɫ", Phoneme::vowel(KS_UH, 131.).lasting(70, 90)),
     (
         "tʃ",
         Phoneme::affricate(
@@ -470,7 +619,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.136, -0.014, 0.149, -0.117, 0.002, 0.093, 0.133,
             ],
             100., // toned down
-        ),
+        )
+        .lasting(60, 90),
     ),
     (
         "dʒ",
@@ -481,7 +631,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0.136, -0.014, 0.149, -0.117, 0.002, 0.093, 0.133,
             ],
             600.,
-        ),
+        )
+        .lasting(60, 90),
     ),
     (
         ",", // Not a plosive, but length is reasonable
@@ -491,7 +642,8 @@ const PHONEMES: &[(&'static str, Phoneme)] = &[
                 0., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0.,
             ],
             0.,
-        ),
+        )
+        .lasting(30, 40),
     ),
 ];
 
@@ -504,36 +656,50 @@ pub fn get_phoneme(s: &str) -> Option<&'static Phoneme> {
     None
 }
 
-pub fn parse(s: &str) -> Vec<&'static Phoneme> {
-    let mut result = Vec::new();
-    let mut i = 0;
-    while i < s.len() {
-        for j in (1..=3).rev() {
-            let mut end = i;
-            for _ in 0..j {
-                end = s.ceil_char_boundary(end + 1);
-            }
-            if let Some(phoneme) = get_phoneme(&s[i..end]) {
-                result.push(phoneme);
-                i = end;
-                break;
-            }
-            if j == 1 {
-                i = end;
-            }
-        }
-    }
-    result
+/// The names of all phonemes in the built-in table.
+pub fn names() -> impl Iterator<Item = &'static str> {
+    PHONEMES.iter().map(|(name, _)| *name)
+}
+
+/// Whether a phoneme matches a pattern, which is either its name, its kind,
+/// such as `vowel`, or `*` for any phoneme.
+pub fn matches(pattern: &str, name: &str, phoneme: &Phoneme) -> bool {
+    pattern == "*" || pattern == name || Kind::from_name(pattern) == Some(phoneme.kind)
 }
 
 impl Kind {
-    pub fn blends(self, other: Kind) -> f64 {
-        match (self, other) {
-            (Kind::Vowel, Kind::Vowel) => 1.0,
-            (Kind::Nasal, Kind::Vowel) => 0.2,
-            (Kind::Vowel, Kind::Nasal) => 0.05,
-            (Kind::Nasal, Kind::Nasal) => 0.2,
-            _ => 0.0,
+    /// The durations of phonemes of this kind which have none of their own,
+    /// matching the envelopes of [`Sequence`](crate::sequence::Sequence).
+    pub fn default_duration(self) -> PhoneDuration {
+        let (min_duration, inherent_duration) = match self {
+            Kind::Plosive => (30, 40),
+            Kind::Affricate => (60, 90),
+            _ => (70, 130),
+        };
+        PhoneDuration {
+            min_duration,
+            inherent_duration,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Vowel => "vowel",
+            Kind::Fricative => "fricative",
+            Kind::Nasal => "nasal",
+            Kind::Plosive => "plosive",
+            Kind::Affricate => "affricate",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Kind> {
+        match name {
+            "vowel" => Some(Kind::Vowel),
+            "fricative" => Some(Kind::Fricative),
+            "nasal" => Some(Kind::Nasal),
+            "plosive" => Some(Kind::Plosive),
+            "affricate" => Some(Kind::Affricate),
+            _ => None,
         }
     }
 }
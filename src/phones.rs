@@ -1,6 +1,7 @@
 //! Data for phones.
 
 use bitflags::bitflags;
+use serde::{Deserialize, Serialize};
 
 use crate::klatt::KlattParams;
 
@@ -12,7 +13,7 @@ use crate::klatt::KlattParams;
 /// The list taken from Appendix B of the book, in alphabetical order. However, qq has
 /// been added, as it's referenced elsewhere but is missing from that table.
 #[repr(u8)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Phone {
     Aa,
     Ae,
@@ -79,38 +80,92 @@ pub enum Phone {
 
 const N_PHONE: usize = 61;
 
+/// Correspondence between concise IPA and phones.
+///
+/// Diphthongs are listed so that they take precedence over their first
+/// element when parsing.
+const IPA_PHONES: &[(&str, Phone)] = &[
+    ("aɪ", Phone::Ay),
+    ("aw", Phone::Aw),
+    ("aʊ", Phone::Aw),
+    ("ɛɪ", Phone::Ey),
+    ("eɪ", Phone::Ey),
+    ("ɔɪ", Phone::Oy),
+    ("oʊ", Phone::Ow),
+    ("tʃ", Phone::Ch),
+    ("dʒ", Phone::Jj),
+    ("ɑ", Phone::Aa),
+    ("a", Phone::Aa),
+    ("æ", Phone::Ae),
+    ("ʌ", Phone::Ah),
+    ("ɔ", Phone::Ao),
+    ("ə", Phone::Ax),
+    ("ɚ", Phone::Er),
+    ("ɛ", Phone::Eh),
+    ("e", Phone::Ey),
+    ("ɪ", Phone::Ih),
+    ("i", Phone::Iy),
+    ("o", Phone::Ow),
+    ("ʊ", Phone::Uh),
+    ("u", Phone::Uw),
+    ("b", Phone::Bb),
+    ("d", Phone::Dd),
+    ("ð", Phone::Dh),
+    ("f", Phone::Ff),
+    ("g", Phone::Gg),
+    ("h", Phone::Hh),
+    ("k", Phone::Kk),
+    ("l", Phone::Ll),
+    ("m", Phone::Mm),
+    ("n", Phone::Nn),
+    ("ŋ", Phone::Ng),
+    ("p", Phone::Pp),
+    ("ɹ", Phone::Rr),
+    ("s", Phone::Ss),
+    ("ʃ", Phone::Sh),
+    ("t", Phone::Tt),
+    ("θ", Phone::Th),
+    ("v", Phone::Vv),
+    ("w", Phone::Ww),
+    ("ʍ", Phone::Wh),
+    ("j", Phone::Yy),
+    ("z", Phone::Zz),
+    ("ʒ", Phone::Zh),
+    (",", Phone::Sil),
+];
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ParamTarget {
     // Actually not 100% sure what the distinction is between av and avc,
     // it doesn't seem to be explained in the book.
-    #[allow(unused)]
-    av: u8,
-    avc: u8,
-    asp: u8,
-    af: u8,
-    a2: u8,
-    a3: u8,
-    a4: u8,
-    a5: u8,
-    a6: u8,
-    ab: u8,
-    f1: u16,
-    f2: u16,
-    f3: u16,
-    f4: u16,
-    b1: u16,
-    b2: u16,
-    b3: u16,
+    pub av: u8,
+    pub avc: u8,
+    pub asp: u8,
+    pub af: u8,
+    pub a2: u8,
+    pub a3: u8,
+    pub a4: u8,
+    pub a5: u8,
+    pub a6: u8,
+    pub ab: u8,
+    pub f1: u16,
+    pub f2: u16,
+    pub f3: u16,
+    pub f4: u16,
+    pub b1: u16,
+    pub b2: u16,
+    pub b3: u16,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ParamTargetGlide {
-    f1: u16,
-    f2: u16,
-    f3: u16,
+    pub f1: u16,
+    pub f2: u16,
+    pub f3: u16,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct PhoneDuration {
-    #[expect(unused, reason = "will be used in phonet rules")]
     pub min_duration: u16,
     pub inherent_duration: u16,
 }
@@ -164,6 +219,76 @@ const fn inv_map(phones: &[Phone]) -> [u8; N_PHONE] {
 }
 
 impl Phone {
+    /// All phones, in order.
+    pub const ALL: [Phone; N_PHONE] = [
+        Phone::Aa,
+        Phone::Ae,
+        Phone::Ah,
+        Phone::Ao,
+        Phone::Aw,
+        Phone::Ax,
+        Phone::Axp,
+        Phone::Axr,
+        Phone::Ay,
+        Phone::Bb,
+        Phone::Ch,
+        Phone::Dd,
+        Phone::Dh,
+        Phone::Dx,
+        Phone::Eh,
+        Phone::El,
+        Phone::Em,
+        Phone::En,
+        Phone::Er,
+        Phone::Exr,
+        Phone::Ey,
+        Phone::Ff,
+        Phone::Gg,
+        Phone::Gp,
+        Phone::Hh,
+        Phone::Hx,
+        Phone::Ih,
+        Phone::Ix,
+        Phone::Ixr,
+        Phone::Iy,
+        Phone::Jj,
+        Phone::Kk,
+        Phone::Kp,
+        Phone::Ll,
+        Phone::Lx,
+        Phone::Mm,
+        Phone::Ng,
+        Phone::Nn,
+        Phone::Ow,
+        Phone::Oxr,
+        Phone::Oy,
+        Phone::Pp,
+        Phone::Qq,
+        Phone::Rr,
+        Phone::Rx,
+        Phone::Sh,
+        Phone::Sil,
+        Phone::Ss,
+        Phone::Th,
+        Phone::Tq,
+        Phone::Tt,
+        Phone::Uh,
+        Phone::Uw,
+        Phone::Uxr,
+        Phone::Vv,
+        Phone::Wh,
+        Phone::Ww,
+        Phone::Yu,
+        Phone::Yy,
+        Phone::Zh,
+        Phone::Zz,
+    ];
+
+    /// The name of the phone, as accepted by [`Phone::parse`].
+    pub fn name(self) -> String {
+        format!("{self:?}").to_ascii_lowercase()
+    }
+
     pub fn parse(name: &str) -> Option<Self> {
         match name {
             "aa" => Some(Self::Aa),
@@ -231,6 +356,33 @@ impl Phone {
         }
     }
 
+    /// Parse a string of concise IPA, as produced by text to phoneme.
+    ///
+    /// Unknown symbols are skipped.
+    pub fn parse_ipa(s: &str) -> Vec<Self> {
+        let mut result = Vec::new();
+        let mut i = 0;
+        while i < s.len() {
+            let mut matched = false;
+            for j in (1..=2).rev() {
+                let mut end = i;
+                for _ in 0..j {
+                    end = s.ceil_char_boundary(end + 1);
+                }
+                if let Some((_, phone)) = IPA_PHONES.iter().find(|(name, _)| *name == &s[i..end]) {
+                    result.push(*phone);
+                    i = end;
+                    matched = true;
+                    break;
+                }
+            }
+            if !matched {
+                i = s.ceil_char_boundary(i + 1);
+            }
+        }
+        result
+    }
+
     pub fn flags(self) -> Flags {
         PHONE_FLAGS[self as u8 as usize]
     }
@@ -248,6 +400,7 @@ impl Phone {
 }
 
 impl ParamTarget {
+    #[allow(clippy::too_many_arguments)]
     const fn new(
         av: u8,
         avc: u8,
@@ -1,52 +1,139 @@
 //! Phoneme to Klatt parameters.
 
+use std::collections::VecDeque;
+
 use crate::{
     klatt::KlattParams,
-    phones::{Flags, Phone, nonvocalic_target, vocalic_target, vocalic_target_glide},
+    phones::{Flags, Phone},
+    utterance::{self, Event, Prosody, Segment},
+    voice::KlattVoice,
 };
 
 /// A state machine to convert a sequence of phonemes into frames for
 /// Klatt synthesis.
-#[derive(Default)]
-pub struct Phonet {
+pub struct Phonet<'a> {
+    voice: &'a KlattVoice,
     // Note: this will become a window
     cur_phone: Option<Phone>,
     phone_dur: u16,
     /// Time in ms since phone start
     time_rel: u16,
+    prosody: Prosody,
+    events: VecDeque<Event<Phone>>,
+    /// Vary the pitch of vowels with their F1.
+    f1_pitch: bool,
+    /// Speaking rate, in addition to the prosody of each phone.
+    rate: f64,
 }
 
 /// Frame time in ms
 const FRAME_TIME: u16 = 5;
 
-impl Phonet {
+/// Duration of a silence phone in ms, as it has no entry in table 9-1.
+const SIL_DURATION: u16 = 40;
+
+impl<'a> Phonet<'a> {
+    pub fn new(voice: &'a KlattVoice) -> Self {
+        Self {
+            voice,
+            cur_phone: None,
+            phone_dur: 0,
+            time_rel: 0,
+            prosody: Prosody::default(),
+            events: VecDeque::new(),
+            f1_pitch: false,
+            rate: 1.0,
+        }
+    }
+
+    /// Set the speaking rate, where larger values are faster.
+    ///
+    /// This multiplies the rate of the prosody, so doesn't change pauses.
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+    }
+
+    /// Vary the pitch of each vowel inversely with its F1.
+    pub fn set_f1_pitch(&mut self, f1_pitch: bool) {
+        self.f1_pitch = f1_pitch;
+    }
+
     pub fn inp_ready(&self) -> bool {
         self.cur_phone.is_none()
     }
 
     pub fn push_phone(&mut self, phone: Phone) {
-        if let Some(dur) = crate::phones::phone_duration(phone) {
+        let dur = match self.voice.duration(phone) {
+            Some(dur) => Some(dur.inherent_duration),
+            None => matches!(phone, Phone::Sil).then_some(SIL_DURATION),
+        };
+        if let Some(dur) = dur {
+            self.events.push_back(Event::Phone(phone));
             self.cur_phone = Some(phone);
-            self.phone_dur = dur.inherent_duration;
+            self.phone_dur = ((dur as f64 / (self.prosody.rate * self.rate)).round() as u16).max(1);
             self.time_rel = 0;
         }
     }
 
+    pub fn push_segment(&mut self, segment: Segment<Phone>) {
+        match segment {
+            Segment::Phone(phone, prosody) => {
+                self.prosody = prosody;
+                self.push_phone(phone);
+            }
+            Segment::Silence(ms) => {
+                self.cur_phone = Some(Phone::Sil);
+                self.phone_dur = ms.clamp(1, u16::MAX as u32) as u16;
+                self.time_rel = 0;
+            }
+            Segment::Event(event) => self.events.push_back(event),
+        }
+    }
+
+    /// Get the next event, if any.
+    ///
+    /// Events are timed at the start of the next frame, so should be drained
+    /// before calling [`Phonet::get_frame`].
+    pub fn next_event(&mut self) -> Option<Event<Phone>> {
+        self.events.pop_front()
+    }
+
     pub fn get_frame(&mut self, out: &mut KlattParams) {
         if let Some(phone) = self.cur_phone {
-            let target = vocalic_target(phone).or_else(|| nonvocalic_target(phone));
-            if let Some(target) = target {
+            if let Some(target) = self.voice.target(phone) {
                 target.update(out);
-                if let Some(glide) = vocalic_target_glide(phone) {
+                if let Some(glide) = self.voice.glide(phone) {
                     let t = self.time_rel as f32 / self.phone_dur as f32;
                     glide.lerp(target, out, t);
                 }
+                // Silence has no level in dB, so its sources are turned off below.
+                let silent = self.prosody.volume <= 0.0;
+                let gain = if silent {
+                    0.
+                } else {
+                    20. * self.prosody.volume.log10() as f32
+                };
                 if phone.is(Flags::VOICED) {
-                    out.f0 = 160.;
-                    out.av += 40.;
+                    out.f0 = 160. * self.prosody.pitch as f32;
+                    if self.f1_pitch && phone.is(Flags::VOWEL) {
+                        out.f0 *= utterance::f1_pitch(target.f1 as f64) as f32;
+                    }
+                    out.av += 40. + gain;
                 } else {
                     out.f0 = 0.;
                 }
+                if out.af > 0. {
+                    out.af += gain;
+                }
+                if out.ah > 0. {
+                    out.ah += gain;
+                }
+                if silent {
+                    out.av = 0.;
+                    out.avs = 0.;
+                    out.af = 0.;
+                    out.ah = 0.;
+                }
             }
             self.time_rel += FRAME_TIME;
             if self.time_rel >= self.phone_dur {
@@ -0,0 +1,91 @@
+//! Pitch detection and voicing decision.
+//!
+//! The period is found by autocorrelation, and a frame is considered voiced
+//! if the correlation at that period is strong, the spectrum is not that of
+//! a fricative, and the frame is not silent.
+
+use crate::lpc::{self, Reflector};
+
+/// Lowest detectable fundamental frequency, in Hz.
+const MIN_F0: f64 = 60.0;
+/// Highest detectable fundamental frequency, in Hz.
+const MAX_F0: f64 = 400.0;
+/// Minimum normalized autocorrelation for a voiced frame.
+const VOICING_THRESHOLD: f64 = 0.4;
+/// A submultiple of the best period is chosen if its correlation is at least
+/// this fraction of the best, to avoid octave errors.
+const SUBMULTIPLE_RATIO: f64 = 0.85;
+/// Frames with a lower RMS than this are silent, in 16 bit sample units.
+const SILENCE_RMS: f64 = 50.0;
+
+pub struct PitchTracker {
+    sample_rate: u32,
+    min_period: usize,
+    max_period: usize,
+}
+
+impl PitchTracker {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            min_period: (sample_rate as f64 / MAX_F0).floor() as usize,
+            max_period: (sample_rate as f64 / MIN_F0).ceil() as usize,
+        }
+    }
+
+    /// Analyze one window of samples, returning the period if voiced.
+    ///
+    /// The reflector should be the LPC analysis of the same window, without
+    /// pre-emphasis. The window should span at least two periods of the
+    /// lowest pitch to be detected.
+    pub fn analyze(&self, buf: &[f64], reflector: &Reflector) -> Option<usize> {
+        let max_period = self.max_period.min(buf.len() / 2);
+        let mut best = (0, 0.0);
+        for period in self.min_period..=max_period {
+            let c = lpc::confidence(buf, period);
+            if c > best.1 {
+                best = (period, c);
+            }
+        }
+        for div in [4, 3, 2] {
+            let period = (best.0 as f64 / div as f64).round() as usize;
+            if period < self.min_period {
+                continue;
+            }
+            // The peak may be a sample away from the exact submultiple.
+            let sub = (period - 1..=period + 1)
+                .map(|p| (p, lpc::confidence(buf, p)))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            if sub.1 >= SUBMULTIPLE_RATIO * best.1 {
+                best = sub;
+                break;
+            }
+        }
+        let (period, confidence) = best;
+        let voiced = confidence > VOICING_THRESHOLD
+            && reflector.is_low_pass()
+            && reflector.rms() > SILENCE_RMS;
+        voiced.then_some(period)
+    }
+
+    /// The fundamental frequency for a period, in Hz.
+    pub fn f0(&self, period: usize) -> f64 {
+        self.sample_rate as f64 / period as f64
+    }
+}
+
+/// Smooth a track of periods, with a median filter over three frames.
+///
+/// This removes isolated octave errors. Only runs of voiced frames are
+/// smoothed, so voicing decisions are unaffected.
+pub fn smooth(periods: &mut [Option<usize>]) {
+    let orig = periods.to_vec();
+    for i in 1..periods.len().saturating_sub(1) {
+        if let (Some(a), Some(b), Some(c)) = (orig[i - 1], orig[i], orig[i + 1]) {
+            let mut p = [a, b, c];
+            p.sort();
+            periods[i] = Some(p[1]);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! A speech queue with priorities and interruption.
+//!
+//! This is the core behavior needed by a screen reader: messages are queued
+//! and spoken in order, speech can be stopped at any time, and urgent
+//! messages preempt whatever is currently being spoken. A preempted message
+//! is resumed from the start of the interrupted word once the more urgent
+//! ones have been spoken.
+
+use std::{collections::VecDeque, ops::Range};
+
+use crate::{
+    phonemes::Phoneme,
+    sequence::Sequence,
+    utterance::{self, Segment},
+    voice::LpcVoice,
+};
+
+/// Priority of a queued message.
+///
+/// Messages are spoken highest priority first, and in the order they were
+/// enqueued within the same priority. A message with a higher priority than
+/// the one currently being spoken preempts it.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Priority {
+    Normal,
+    High,
+}
+
+/// Notifications of progress through the queue.
+///
+/// Each message is identified by the index returned from [`SpeechQueue::enqueue`].
+#[derive(Clone)]
+pub enum Event {
+    /// The message has started speaking, or resumed after being preempted.
+    Start(u32),
+    /// The message has reached segment `pos` out of `len`.
+    Progress { index: u32, pos: usize, len: usize },
+    /// The message has started speaking the word at `src` in its text.
+    Word { index: u32, src: Range<usize> },
+    /// The message has reached a named mark.
+    Mark { index: u32, name: String },
+    /// The message has finished speaking.
+    End(u32),
+    /// The message was preempted, and will resume later.
+    Preempted(u32),
+    /// The message was stopped before finishing.
+    Cancelled(u32),
+}
+
+struct Message<'a> {
+    index: u32,
+    priority: Priority,
+    segments: Vec<Segment<&'a Phoneme>>,
+    /// The segment to start speaking from, after the message was preempted.
+    start: usize,
+}
+
+struct Current<'a> {
+    msg: Message<'a>,
+    seq: Sequence<'a, Vec<Segment<&'a Phoneme>>>,
+    pos: usize,
+    /// True if fading out to make way for a more urgent message, rather than
+    /// being stopped.
+    preempted: bool,
+}
+
+/// Length of the fade-out when speech is interrupted, in samples.
+///
+/// Cutting off the waveform abruptly would cause an audible click.
+const FADE_LEN: usize = 160;
+
+/// A queue of messages, spoken with phonemes borrowed from a voice.
+pub struct SpeechQueue<'a> {
+    voice: &'a LpcVoice,
+    queue: VecDeque<Message<'a>>,
+    current: Option<Current<'a>>,
+    /// Samples remaining in the fade-out of the current message, if stopping.
+    fade: Option<usize>,
+    next_index: u32,
+    callback: Option<Box<dyn FnMut(Event)>>,
+}
+
+impl<'a> SpeechQueue<'a> {
+    pub fn new(voice: &'a LpcVoice) -> Self {
+        Self {
+            voice,
+            queue: VecDeque::new(),
+            current: None,
+            fade: None,
+            next_index: 0,
+            callback: None,
+        }
+    }
+
+    /// Set a callback to be notified of progress.
+    ///
+    /// The callback is called from [`SpeechQueue::get`], so the events are
+    /// synchronized with the sample stream.
+    pub fn set_callback(&mut self, callback: impl FnMut(Event) + 'static) {
+        self.callback = Some(Box::new(callback));
+    }
+
+    /// Add a message to the queue, returning its index.
+    pub fn enqueue(&mut self, segments: Vec<Segment<&'a Phoneme>>, priority: Priority) -> u32 {
+        let index = self.next_index;
+        self.next_index = self.next_index.wrapping_add(1);
+        let pos = self
+            .queue
+            .iter()
+            .position(|msg| msg.priority < priority)
+            .unwrap_or(self.queue.len());
+        self.queue.insert(
+            pos,
+            Message {
+                index,
+                priority,
+                segments,
+                start: 0,
+            },
+        );
+        if let Some(current) = &mut self.current
+            && current.msg.priority < priority
+            && self.fade.is_none()
+        {
+            current.preempted = true;
+            self.fade_out();
+        }
+        index
+    }
+
+    /// Stop speaking and discard all queued messages.
+    pub fn stop(&mut self) {
+        while let Some(msg) = self.queue.pop_front() {
+            self.notify(Event::Cancelled(msg.index));
+        }
+        if let Some(current) = &mut self.current {
+            current.preempted = false;
+        }
+        self.fade_out();
+    }
+
+    /// True if there is nothing being spoken or waiting to be spoken.
+    pub fn is_idle(&self) -> bool {
+        self.current.is_none() && self.queue.is_empty()
+    }
+
+    /// Get the next sample.
+    ///
+    /// Returns `None` when the queue is idle.
+    pub fn get(&mut self) -> Option<f64> {
+        loop {
+            if self.current.is_none() {
+                let msg = self.queue.pop_front()?;
+                self.notify(Event::Start(msg.index));
+                let mut seq = Sequence::new(msg.segments[msg.start..].to_vec());
+                seq.set_voice(self.voice);
+                self.current = Some(Current {
+                    msg,
+                    seq,
+                    pos: usize::MAX,
+                    preempted: false,
+                });
+            }
+            let current = self.current.as_mut().unwrap();
+            if let Some(y) = current.seq.get() {
+                let index = current.msg.index;
+                let pos = current.msg.start + current.seq.position();
+                let len = current.msg.segments.len();
+                let changed = pos != current.pos;
+                current.pos = pos;
+                if changed && pos < len {
+                    self.notify(Event::Progress { index, pos, len });
+                }
+                while let Some(event) = self.current.as_mut().unwrap().seq.next_event() {
+                    match event {
+                        utterance::Event::WordStart(src) => self.notify(Event::Word { index, src }),
+                        utterance::Event::Mark(name) => self.notify(Event::Mark { index, name }),
+                        _ => (),
+                    }
+                }
+                if let Some(fade) = &mut self.fade {
+                    *fade -= 1;
+                    let level = *fade as f64 * (1.0 / FADE_LEN as f64);
+                    if *fade == 0 {
+                        self.fade = None;
+                        let current = self.current.take().unwrap();
+                        if current.preempted {
+                            self.resume_later(current.msg, pos);
+                            self.notify(Event::Preempted(index));
+                        } else {
+                            self.notify(Event::Cancelled(index));
+                        }
+                    }
+                    return Some(y * level);
+                }
+                return Some(y);
+            }
+            let index = current.msg.index;
+            self.current = None;
+            self.fade = None;
+            self.notify(Event::End(index));
+        }
+    }
+
+    /// Put a preempted message back at the head of its priority, to resume
+    /// from the start of the word being spoken at `pos`.
+    fn resume_later(&mut self, mut msg: Message<'a>, pos: usize) {
+        msg.start = (msg.segments[msg.start..pos.min(msg.segments.len())].iter())
+            .rposition(|seg| matches!(seg, Segment::Event(utterance::Event::WordStart(_))))
+            .map_or(msg.start, |ix| msg.start + ix);
+        let pos = self
+            .queue
+            .iter()
+            .position(|queued| queued.priority <= msg.priority)
+            .unwrap_or(self.queue.len());
+        self.queue.insert(pos, msg);
+    }
+
+    fn fade_out(&mut self) {
+        if self.current.is_some() && self.fade.is_none() {
+            self.fade = Some(FADE_LEN);
+        }
+    }
+
+    fn notify(&mut self, event: Event) {
+        if let Some(callback) = &mut self.callback {
+            callback(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::utterance::Prosody;
+
+    /// A message of `n` words, each a single vowel.
+    fn words(voice: &LpcVoice, n: usize) -> Vec<Segment<&Phoneme>> {
+        let vowel = voice.get("ɑ").unwrap();
+        (0..n)
+            .flat_map(|i| {
+                [
+                    Segment::Event(utterance::Event::WordStart(i..i + 1)),
+                    Segment::Phone(vowel, Prosody::default()),
+                ]
+            })
+            .collect()
+    }
+
+    /// Record the events of a queue as strings.
+    fn record(queue: &mut SpeechQueue) -> Rc<RefCell<Vec<String>>> {
+        let events = Rc::new(RefCell::new(vec![]));
+        let log = events.clone();
+        queue.set_callback(move |event| {
+            let event = match event {
+                Event::Start(index) => format!("start {index}"),
+                Event::Word { index, src } => format!("word {index} {}", src.start),
+                Event::End(index) => format!("end {index}"),
+                Event::Preempted(index) => format!("preempted {index}"),
+                Event::Cancelled(index) => format!("cancelled {index}"),
+                Event::Progress { .. } | Event::Mark { .. } => return,
+            };
+            log.borrow_mut().push(event);
+        });
+        events
+    }
+
+    /// Get samples until the given event has been reported.
+    fn run_until(queue: &mut SpeechQueue, events: &RefCell<Vec<String>>, event: &str) {
+        while !events.borrow().iter().any(|e| e == event) {
+            queue.get().unwrap();
+        }
+    }
+
+    #[test]
+    fn preempted_message_resumes() {
+        let voice = LpcVoice::builtin();
+        let mut queue = SpeechQueue::new(&voice);
+        let events = record(&mut queue);
+        queue.enqueue(words(&voice, 3), Priority::Normal);
+        queue.enqueue(words(&voice, 1), Priority::Normal);
+        run_until(&mut queue, &events, "word 0 1");
+        queue.enqueue(words(&voice, 1), Priority::High);
+        while queue.get().is_some() {}
+        assert_eq!(
+            *events.borrow(),
+            [
+                "start 0",
+                "word 0 0",
+                "word 0 1",
+                "preempted 0",
+                "start 2",
+                "word 2 0",
+                "end 2",
+                "start 0",
+                "word 0 1",
+                "word 0 2",
+                "end 0",
+                "start 1",
+                "word 1 0",
+                "end 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn stop_cancels_everything() {
+        let voice = LpcVoice::builtin();
+        let mut queue = SpeechQueue::new(&voice);
+        let events = record(&mut queue);
+        queue.enqueue(words(&voice, 2), Priority::Normal);
+        queue.enqueue(words(&voice, 1), Priority::Normal);
+        run_until(&mut queue, &events, "word 0 0");
+        queue.enqueue(words(&voice, 1), Priority::High);
+        queue.stop();
+        let mut n = 0;
+        while queue.get().is_some() {
+            assert!(n < FADE_LEN);
+            n += 1;
+        }
+        assert!(queue.is_idle());
+        assert_eq!(
+            *events.borrow(),
+            [
+                "start 0",
+                "word 0 0",
+                "cancelled 2",
+                "cancelled 1",
+                "cancelled 0",
+            ]
+        );
+    }
+}
@@ -0,0 +1,40 @@
+//! Sample rate conversion.
+//!
+//! This is plain windowed sinc interpolation, evaluated directly at each
+//! output sample. It's slow, but only used for preparing recordings.
+
+use std::f64::consts::PI;
+
+/// Number of zero crossings of the sinc on each side of a sample.
+const ZERO_CROSSINGS: usize = 16;
+
+/// Resample a signal, low-pass filtering it if the rate is reduced.
+pub fn resample(samples: &[f64], from: u32, to: u32) -> Vec<f64> {
+    if from == to {
+        return samples.to_vec();
+    }
+    let ratio = to as f64 / from as f64;
+    // Cutoff relative to the Nyquist frequency of the input.
+    let cutoff = ratio.min(1.0);
+    let half_len = ZERO_CROSSINGS as f64 / cutoff;
+    let n_out = (samples.len() as f64 * ratio).ceil() as usize;
+    (0..n_out)
+        .map(|j| {
+            let t = j as f64 / ratio;
+            let start = (t - half_len).ceil().max(0.0) as usize;
+            let end = ((t + half_len).floor() as usize).min(samples.len().saturating_sub(1));
+            (start..=end)
+                .map(|i| {
+                    let x = (t - i as f64) * cutoff;
+                    let sinc = if x == 0.0 {
+                        1.0
+                    } else {
+                        (PI * x).sin() / (PI * x)
+                    };
+                    let window = 0.5 + 0.5 * (PI * (t - i as f64) / half_len).cos();
+                    samples[i] * cutoff * sinc * window
+                })
+                .sum()
+        })
+        .collect()
+}
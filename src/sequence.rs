@@ -1,14 +1,67 @@
+use std::{collections::VecDeque, fmt};
+
 use crate::{
-    phonemes::{Kind, Phoneme},
+    lpc, lsp,
+    phonemes::{Kind, Phoneme, Stop},
     synth::{Params, Synth},
+    transition::{self, Transition},
+    utterance::{self, Event, Prosody, Segment},
+    voice::LpcVoice,
 };
 
-pub struct Sequence<T: AsRef<[&'static Phoneme]>> {
+pub struct Sequence<'a, T: AsRef<[Segment<&'a Phoneme>]>> {
     seq: T,
     ix: usize,
     env: Env,
     env_ix: usize,
     synth: Synth,
+    events: VecDeque<Event<&'a Phoneme>>,
+    /// LSP frequencies of the current and next phonemes, while blending or
+    /// releasing a plosive.
+    blend_lsp: Option<lsp::Blend>,
+    /// LSP frequencies of the start and end of the current diphthong.
+    glide_lsp: Option<lsp::Blend>,
+    /// The transition out of a segment, found once for its index.
+    next_transition: Option<(usize, Option<Transition>)>,
+    contour: Vec<ContourPoint>,
+    /// Number of samples produced so far.
+    time: usize,
+    /// The pitch period, smoothed towards that of the current phoneme.
+    period: f64,
+    /// Pitch multiplier for each segment, if pitch follows F1.
+    f1_pitch: Vec<f64>,
+    /// The voice of the phonemes, for its transitions.
+    voice: Option<&'a LpcVoice>,
+    /// Duration of each segment in ms, before scaling by the rate.
+    durations: Vec<f64>,
+    /// Speaking rate, in addition to the prosody of each phoneme.
+    rate: f64,
+}
+
+/// A point of a prosody contour, in addition to the prosody of each phoneme.
+///
+/// The contour is interpolated linearly between points, and holds its value
+/// before the first point and after the last.
+#[derive(Clone, Copy, Debug)]
+pub struct ContourPoint {
+    /// Time from the start of the sequence, in seconds.
+    pub time: f64,
+    /// Fundamental frequency, in Hz.
+    pub f0: f64,
+    /// Amplitude multiplier.
+    pub volume: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ContourError {
+    /// The line number, counting from 1.
+    pub line: usize,
+}
+
+impl fmt::Display for ContourError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid contour point at line {}", self.line)
+    }
 }
 
 struct Env {
@@ -33,8 +86,8 @@ impl Env {
             return self.sustain_level;
         }
         ix -= self.sustain_len;
-        if ix < self.decay_len {
-            return self.sustain_level * (1.0 - ix as f64 / self.decay_len as f64);
+        if ix < self.release_len {
+            return self.sustain_level * (1.0 - ix as f64 / self.release_len as f64);
         }
         0.0
     }
@@ -42,13 +95,72 @@ impl Env {
     fn len(&self) -> usize {
         self.attack_len + self.decay_len + self.sustain_len + self.release_len
     }
+
+    /// Time at which the release starts.
+    fn release_start(&self) -> usize {
+        self.len() - self.release_len
+    }
 }
 
 const VOLUME: f64 = 5e-4;
-const BLEND_LEN: usize = 1500;
+const SAMPLE_RATE: f64 = 16_000.0;
+/// Fundamental frequency of the voice, in Hz.
+const F0: f64 = SAMPLE_RATE / 140.0;
+/// Fraction of the remaining distance to the target period covered in each
+/// sample, for a time constant of about 20 ms.
+const PERIOD_SMOOTHING: f64 = 1.0 / 320.0;
+const SAMPLES_PER_MS: u32 = 16;
+/// Fraction of a diphthong which holds its first vowel before gliding.
+const GLIDE_HOLD: f64 = 0.3;
+/// Spectrum of the voice bar during the closure of a voiced plosive, which
+/// only passes low frequencies.
+const VOICE_BAR: [f64; 18] = {
+    let mut k = [0.0; 18];
+    k[0] = -0.9;
+    k
+};
+/// Level of the voice bar, relative to the voicing of the plosive.
+const VOICE_BAR_LEVEL: f64 = 0.2;
+/// Level of the burst of a plosive, relative to its RMS.
+const BURST_LEVEL: f64 = 0.4;
+/// Level of aspiration at the end of a release, relative to the voicing of
+/// the next phoneme.
+const ASPIRATION_LEVEL: f64 = 0.5;
+/// Level at the start of the release of a voiced plosive, relative to the
+/// next phoneme.
+const VOICED_RELEASE_LEVEL: f64 = 0.3;
+
+// Context rules for durations, as multipliers of the part of the inherent
+// duration above the minimum, after the rules in the book.
+/// The last vowel before a pause.
+const PAUSE_LENGTHENING: f64 = 1.4;
+/// A vowel which is not in the last syllable of its word.
+const NON_FINAL_SHORTENING: f64 = 0.85;
+/// A vowel next to another in the same word, as in a diphthong.
+const VOWEL_SEQUENCE_SHORTENING: f64 = 0.6;
+/// A vowel before a voiceless plosive or affricate in the same word.
+const VOICELESS_STOP_SHORTENING: f64 = 0.7;
+/// A vowel before a voiced fricative in the same word.
+const VOICED_FRICATIVE_LENGTHENING: f64 = 1.6;
+/// A vowel before a voiced plosive or affricate in the same word.
+const VOICED_STOP_LENGTHENING: f64 = 1.2;
+/// A vowel before a nasal in the same word.
+const NASAL_SHORTENING: f64 = 0.85;
+/// A consonant next to another in the same word.
+const CLUSTER_SHORTENING: f64 = 0.7;
 
-impl<T: AsRef<[&'static Phoneme]>> Sequence<T> {
+impl<'a, T: AsRef<[Segment<&'a Phoneme>]>> Sequence<'a, T> {
     pub fn new(seq: T) -> Self {
+        let durations = durations(seq.as_ref());
+        // Voice files can have any order, so size the filter for the largest,
+        // including the built-in voice bar.
+        let order = (seq.as_ref().iter())
+            .filter_map(|seg| match seg {
+                Segment::Phone(ph, _) => Some(ph),
+                _ => None,
+            })
+            .flat_map(|ph| [ph.ks.len(), ph.glide.as_ref().map_or(0, |g| g.len())])
+            .fold(VOICE_BAR.len(), usize::max);
         Self {
             seq,
             ix: 0,
@@ -60,58 +172,371 @@ impl<T: AsRef<[&'static Phoneme]>> Sequence<T> {
                 release_len: 500,
             },
             env_ix: 0,
-            synth: Synth::new(18),
+            synth: Synth::new(order),
+            events: VecDeque::new(),
+            blend_lsp: None,
+            glide_lsp: None,
+            next_transition: None,
+            contour: vec![],
+            time: 0,
+            period: 0.0,
+            f1_pitch: vec![],
+            voice: None,
+            durations,
+            rate: 1.0,
         }
     }
 
+    /// Set the speaking rate, where larger values are faster.
+    ///
+    /// This multiplies the rate of the prosody, so doesn't change pauses.
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+    }
+
+    /// Use the transitions and excitation of a voice, rather than the
+    /// built-in ones.
+    pub fn set_voice(&mut self, voice: &'a LpcVoice) {
+        self.voice = Some(voice);
+        self.synth.set_excitation(voice.excitation(), SAMPLE_RATE);
+    }
+
+    /// Modulate the noise of voiced phonemes with the pitch period.
+    pub fn set_modulated_noise(&mut self, modulated_noise: bool) {
+        self.synth.set_modulated_noise(modulated_noise);
+    }
+
+    /// Vary the pitch of each vowel inversely with its F1.
+    ///
+    /// F1 is estimated once for each vowel, from its spectrum.
+    pub fn set_f1_pitch(&mut self, f1_pitch: bool) {
+        self.f1_pitch = if f1_pitch {
+            let segments = self.seq.as_ref().iter();
+            segments
+                .map(|segment| match segment {
+                    Segment::Phone(phoneme, _) if phoneme.kind == Kind::Vowel => {
+                        lpc::first_formant(&phoneme.ks, SAMPLE_RATE as u32)
+                            .map_or(1.0, utterance::f1_pitch)
+                    }
+                    _ => 1.0,
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+    }
+
+    /// Set a contour of pitch and volume for the whole sequence.
+    ///
+    /// The F0 of the contour replaces that of the voice, and both are
+    /// multiplied by the prosody of each phoneme.
+    pub fn set_contour(&mut self, contour: Vec<ContourPoint>) {
+        self.contour = contour;
+    }
+
+    /// Get the next event, if any.
+    ///
+    /// Events are timed at the start of the sample most recently returned
+    /// from [`Sequence::get`], so should be drained after each call.
+    pub fn next_event(&mut self) -> Option<Event<&'a Phoneme>> {
+        self.events.pop_front()
+    }
+
     pub fn get(&mut self) -> Option<f64> {
         let seq = self.seq.as_ref();
+        while let Some(Segment::Event(event)) = seq.get(self.ix) {
+            self.events.push_back(event.clone());
+            self.ix += 1;
+        }
         if self.ix == seq.len() {
             return None;
         }
-        let phoneme = &seq[self.ix];
-        let k = phoneme.ks.to_vec();
-        let period = if phoneme.voiced { 140 } else { 0 };
-        let rms = phoneme.rms * VOLUME;
-        let params = Params { k, period, rms };
+        self.time += 1;
+        let (phoneme, prosody) = match seq[self.ix] {
+            Segment::Phone(phoneme, prosody) => (phoneme, self.prosody(self.ix, prosody)),
+            Segment::Event(_) => unreachable!(),
+            Segment::Silence(ms) => {
+                self.env_ix += 1;
+                if self.env_ix >= ms as usize * SAMPLES_PER_MS as usize {
+                    self.ix += 1;
+                    self.env_ix = 0;
+                }
+                return Some(0.0);
+            }
+        };
+        let mut params = self.params(phoneme, prosody);
         if self.env_ix == 0 {
-            self.env = self.env_for_phoneme(phoneme);
-        }
-        if let Some(next) = seq.get(self.ix + 1) {
-            let blend = phoneme.kind.blends(next.kind);
-            if blend > 0.0 && self.env_ix >= 600 {
-                let next_k = next.ks.to_vec();
-                let next_rms = next.rms * VOLUME;
-                let next_params = Params {
-                    k: next_k,
-                    period,
-                    rms: next_rms,
-                };
-                let t = (self.env_ix - 600) as f64 / BLEND_LEN as f64;
-                let t = (0.5 + (t - 0.5) / blend).clamp(0., 1.);
-                let blend_params = params.lerp(&next_params, t);
+            self.env = self.env_for_phoneme(phoneme, prosody, self.durations[self.ix]);
+            self.events.push_back(Event::Phone(phoneme));
+        }
+        // Events between phonemes don't interrupt blending.
+        let next_ix = (self.ix + 1..seq.len())
+            .find(|ix| !matches!(seq[*ix], Segment::Event(_)))
+            .unwrap_or(seq.len());
+        if let Some(stop) = phoneme.stop {
+            return Some(self.get_stop(phoneme, stop, prosody, params, next_ix));
+        }
+        // A diphthong reaches its end before any blend into the next phoneme.
+        let mut glide_end = self.env.len();
+        if let Some(&Segment::Phone(next, next_prosody)) = seq.get(next_ix) {
+            let next_prosody = self.prosody(next_ix, next_prosody);
+            let transition = match self.next_transition {
+                Some((ix, transition)) if ix == self.ix => transition,
+                _ => {
+                    let transition = self.transition(phoneme, next);
+                    self.next_transition = Some((self.ix, transition));
+                    transition
+                }
+            };
+            let transition = transition.map(|tr| {
+                let lead = scale_ms(tr.lead, prosody).min(self.env.len());
+                (tr, self.env.len() - lead)
+            });
+            if let Some((_, blend_start)) = transition {
+                glide_end = blend_start;
+            }
+            if let Some((tr, blend_start)) = transition
+                && self.env_ix >= blend_start
+            {
+                let blend_len = scale_ms(tr.duration, prosody);
+                let next_env = self.env_for_phoneme(next, next_prosody, self.durations[next_ix]);
+                let next_start =
+                    scale_ms(tr.next_start, next_prosody).min(next_env.release_start());
+                let mut next_params = self.params(next, next_prosody);
+                let t_lin = (self.env_ix - blend_start) as f64 / blend_len as f64;
+                let t = tr.weight(t_lin);
+                // Only glide between periods if both phonemes are voiced,
+                // otherwise switch voicing halfway.
+                if (next_params.period == 0.0) != (params.period == 0.0) {
+                    if t < 0.5 {
+                        next_params.period = params.period;
+                    } else {
+                        params.period = next_params.period;
+                    }
+                }
+                if let Some(end) = &phoneme.glide {
+                    params.k = end.to_vec();
+                }
+                let gain = self.env.get(blend_start)
+                    + (next_env.get(next_start) - self.env.get(blend_start)) * t_lin;
+                let mut blend_params = params.lerp(&next_params, t);
+                blend_params.period = smooth_period(&mut self.period, blend_params.period);
+                // Interpolate the spectrum in the LSP domain, when possible.
+                let ks = phoneme.glide.as_ref().unwrap_or(&phoneme.ks);
+                let blend = (self.blend_lsp).get_or_insert_with(|| lsp::Blend::new(ks, &next.ks));
+                if let Some(k) = blend.get(t) {
+                    k.clone_into(&mut blend_params.k);
+                }
                 let y = self.synth.get_sample(&blend_params);
                 self.env_ix += 1;
-                if self.env_ix == 600 + BLEND_LEN {
-                    self.ix += 1;
-                    self.env_ix = 600;
+                if self.env_ix >= blend_start + blend_len {
+                    self.enter(next_ix, next, next_env, next_start);
                 }
-                return Some(y * 0.9);
+                return Some(y * gain);
+            }
+        }
+        if let Some(end) = &phoneme.glide {
+            let t = glide_weight(self.env_ix as f64 / glide_end.max(1) as f64);
+            let glide = (self.glide_lsp).get_or_insert_with(|| lsp::Blend::new(&phoneme.ks, end));
+            match glide.get(t) {
+                Some(k) => k.clone_into(&mut params.k),
+                None => params.k = lerp_ks(&phoneme.ks, end, t),
             }
         }
+        params.period = smooth_period(&mut self.period, params.period);
         // TODO: don't allocate here
         let y = self.synth.get_sample(&params);
         let env_level = self.env.get(self.env_ix);
         self.env_ix += 1;
-        if self.env_ix == self.env.len() {
+        if self.env_ix >= self.env.len() {
             self.ix += 1;
             self.env_ix = 0;
+            self.glide_lsp = None;
         }
         Some(y * env_level)
     }
 
-    fn env_for_phoneme(&self, phoneme: &Phoneme) -> Env {
-        match phoneme.kind {
+    /// Continue with the phoneme at `next_ix`, part way into its envelope.
+    fn enter(&mut self, next_ix: usize, next: &'a Phoneme, env: Env, env_ix: usize) {
+        let seq = self.seq.as_ref();
+        for segment in &seq[self.ix + 1..next_ix] {
+            if let Segment::Event(event) = segment {
+                self.events.push_back(event.clone());
+            }
+        }
+        self.ix = next_ix;
+        self.blend_lsp = None;
+        self.glide_lsp = None;
+        self.env = env;
+        self.env_ix = env_ix;
+        self.events.push_back(Event::Phone(next));
+    }
+
+    /// Get a sample of a plosive with a closure, burst and release.
+    ///
+    /// The release moves towards a following vowel or nasal, which then
+    /// starts at the end of its attack.
+    fn get_stop(
+        &mut self,
+        phoneme: &'a Phoneme,
+        stop: Stop,
+        prosody: Prosody,
+        mut params: Params,
+        next_ix: usize,
+    ) -> f64 {
+        let len = |ms: f64| if ms > 0.0 { scale_ms(ms, prosody) } else { 0 };
+        let burst_start = len(self.durations[self.ix] - stop.burst);
+        let release_start = burst_start + len(stop.burst);
+        let end = release_start + len(stop.release);
+        let next = match self.seq.as_ref().get(next_ix) {
+            Some(&Segment::Phone(next, next_prosody))
+                if next.voiced && matches!(next.kind, Kind::Vowel | Kind::Nasal) =>
+            {
+                Some((next, self.prosody(next_ix, next_prosody)))
+            }
+            _ => None,
+        };
+        let (y, gain) = if self.env_ix < burst_start {
+            if phoneme.voiced {
+                params.k = VOICE_BAR.to_vec();
+                params.noise = 0.0;
+                params.period = smooth_period(&mut self.period, params.period);
+                (self.synth.get_sample(&params), VOICE_BAR_LEVEL)
+            } else {
+                (0.0, 0.0)
+            }
+        } else if self.env_ix < release_start {
+            params.period = 0.0;
+            params.noise = 0.0;
+            (self.synth.get_sample(&params), BURST_LEVEL)
+        } else {
+            let t = (self.env_ix - release_start) as f64 / (end - release_start).max(1) as f64;
+            let (ks, next_params) = match next {
+                Some((next, next_prosody)) => (&next.ks, self.params(next, next_prosody)),
+                None => (&phoneme.ks, params.clone()),
+            };
+            let blend = (self.blend_lsp).get_or_insert_with(|| lsp::Blend::new(&phoneme.ks, ks));
+            let k = match blend.get(t) {
+                Some(k) => k.to_vec(),
+                None => lerp_ks(&phoneme.ks, ks, t),
+            };
+            let gain;
+            if phoneme.voiced {
+                // Voicing starts quietly, with the noise of the burst dying away.
+                let burst_noise = phoneme.noise * next_params.rms / next_params.period.sqrt();
+                params = Params {
+                    k,
+                    period: smooth_period(&mut self.period, next_params.period),
+                    noise: burst_noise * (1.0 - t),
+                    ..next_params
+                };
+                gain = match next {
+                    Some(_) => VOICED_RELEASE_LEVEL + (1.0 - VOICED_RELEASE_LEVEL) * t,
+                    None => 1.0 - t,
+                };
+            } else {
+                // Aspiration is noise through the moving spectrum.
+                let aspiration = match next {
+                    Some(_) => ASPIRATION_LEVEL * next_params.rms / next_params.period.sqrt(),
+                    None => 0.0,
+                };
+                let burst = params.rms * BURST_LEVEL;
+                params.k = k;
+                params.rms = burst + (aspiration - burst) * t;
+                gain = 1.0;
+            }
+            (self.synth.get_sample(&params), gain)
+        };
+        self.env_ix += 1;
+        if self.env_ix >= end {
+            match next {
+                Some((next, next_prosody)) if end > burst_start => {
+                    let env = self.env_for_phoneme(next, next_prosody, self.durations[next_ix]);
+                    let attack_len = env.attack_len;
+                    self.enter(next_ix, next, env, attack_len);
+                }
+                _ => {
+                    self.ix += 1;
+                    self.env_ix = 0;
+                    self.blend_lsp = None;
+                }
+            }
+        }
+        y * gain
+    }
+
+    fn params(&self, phoneme: &Phoneme, prosody: Prosody) -> Params {
+        let (f0, volume) = self.contour_at(self.time as f64 / SAMPLE_RATE);
+        let k = phoneme.ks.to_vec();
+        let period = if phoneme.voiced {
+            SAMPLE_RATE / (f0 * prosody.pitch)
+        } else {
+            0.0
+        };
+        let rms = phoneme.rms * VOLUME * prosody.volume * volume;
+        // Noise has unit RMS, while a pulse train has an RMS of about one over
+        // the square root of the period.
+        let noise = if period > 0.0 {
+            phoneme.noise * rms / period.sqrt()
+        } else {
+            0.0
+        };
+        Params {
+            k,
+            period,
+            rms,
+            noise,
+        }
+    }
+
+    /// The transition between two phonemes, if they blend.
+    fn transition(&self, from: &Phoneme, to: &Phoneme) -> Option<Transition> {
+        match self.voice {
+            Some(voice) => voice.transition(from, to).copied(),
+            None => transition::find_builtin(from, to).copied(),
+        }
+    }
+
+    /// The prosody of a segment, including the rate and the pitch from F1.
+    fn prosody(&self, ix: usize, prosody: Prosody) -> Prosody {
+        Prosody {
+            pitch: prosody.pitch * self.f1_pitch.get(ix).unwrap_or(&1.0),
+            rate: prosody.rate * self.rate,
+            ..prosody
+        }
+    }
+
+    /// The F0 and volume of the contour at a time, in seconds.
+    fn contour_at(&self, t: f64) -> (f64, f64) {
+        let Some(first) = self.contour.first() else {
+            return (F0, 1.0);
+        };
+        let i = self.contour.partition_point(|p| p.time <= t);
+        if i == 0 {
+            return (first.f0, first.volume);
+        }
+        let a = self.contour[i - 1];
+        let Some(b) = self.contour.get(i) else {
+            return (a.f0, a.volume);
+        };
+        let u = (t - a.time) / (b.time - a.time);
+        (
+            a.f0 + (b.f0 - a.f0) * u,
+            a.volume + (b.volume - a.volume) * u,
+        )
+    }
+
+    /// Index of the segment currently being played.
+    pub fn position(&self) -> usize {
+        self.ix
+    }
+
+    /// The envelope of a phoneme lasting `ms`, which is scaled by the rate.
+    ///
+    /// The sustain takes up the duration beyond the attack, decay and
+    /// release, which are shortened if there isn't room for them.
+    fn env_for_phoneme(&self, phoneme: &Phoneme, prosody: Prosody, ms: f64) -> Env {
+        let env = match phoneme.kind {
             Kind::Plosive => Env {
                 attack_len: 160,
                 decay_len: 160,
@@ -133,6 +558,177 @@ impl<T: AsRef<[&'static Phoneme]>> Sequence<T> {
                 sustain_len: 1000,
                 release_len: 500,
             },
+        };
+        let len = scale_ms(ms, prosody);
+        let fixed = env.attack_len + env.decay_len + env.release_len;
+        let fixed_len = scale_len(fixed, prosody);
+        let scale = |n: usize| {
+            let n = scale_len(n, prosody);
+            if fixed_len > len {
+                (n * len / fixed_len).max(1)
+            } else {
+                n
+            }
+        };
+        let mut env = Env {
+            attack_len: scale(env.attack_len),
+            decay_len: scale(env.decay_len),
+            release_len: scale(env.release_len),
+            sustain_len: 0,
+            ..env
+        };
+        env.sustain_len = len.saturating_sub(env.len());
+        env
+    }
+}
+
+/// The duration of each segment in ms, lengthened or shortened for its
+/// context. Segments other than phonemes have a duration of zero here.
+fn durations(seq: &[Segment<&Phoneme>]) -> Vec<f64> {
+    let is_vowel = |segment: &Segment<&Phoneme>| matches!(segment, Segment::Phone(phoneme, _) if phoneme.kind == Kind::Vowel);
+    let is_pause = |segment: &Segment<&Phoneme>| matches!(segment, Segment::Silence(_));
+    let is_word_end = |segment: &Segment<&Phoneme>| {
+        is_pause(segment) || matches!(segment, Segment::Event(Event::WordEnd(_)))
+    };
+    let mut result = vec![0.0; seq.len()];
+    for (ix, segment) in seq.iter().enumerate() {
+        let Segment::Phone(phoneme, _) = segment else {
+            continue;
+        };
+        let prev = utterance::adjacent(seq[..ix].iter().rev()).copied();
+        let next = utterance::adjacent(seq[ix + 1..].iter()).copied();
+        let later = &seq[ix + 1..];
+        let mut pct = 1.0;
+        if phoneme.kind == Kind::Vowel {
+            if !later.iter().take_while(|s| !is_pause(s)).any(is_vowel) {
+                pct *= PAUSE_LENGTHENING;
+            } else if later.iter().take_while(|s| !is_word_end(s)).any(is_vowel)
+                && next.is_none_or(|next| next.kind != Kind::Vowel)
+            {
+                pct *= NON_FINAL_SHORTENING;
+            }
+            if [prev, next].iter().flatten().any(|p| p.kind == Kind::Vowel) {
+                pct *= VOWEL_SEQUENCE_SHORTENING;
+            }
+            pct *= match next.map(|next| (next.kind, next.voiced)) {
+                Some((Kind::Plosive | Kind::Affricate, false)) => VOICELESS_STOP_SHORTENING,
+                Some((Kind::Plosive | Kind::Affricate, true)) => VOICED_STOP_LENGTHENING,
+                Some((Kind::Fricative, true)) => VOICED_FRICATIVE_LENGTHENING,
+                Some((Kind::Nasal, _)) => NASAL_SHORTENING,
+                _ => 1.0,
+            };
+        } else if [prev, next].iter().flatten().any(|p| p.kind != Kind::Vowel) {
+            pct *= CLUSTER_SHORTENING;
+        }
+        let d = phoneme.duration.unwrap_or(phoneme.kind.default_duration());
+        let min = d.min_duration as f64;
+        result[ix] = min + (d.inherent_duration as f64 - min) * pct;
+    }
+    result
+}
+
+/// The progress of a diphthong towards its end, at a fraction `t` of it.
+fn glide_weight(t: f64) -> f64 {
+    let t = ((t - GLIDE_HOLD) / (1.0 - GLIDE_HOLD)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp_ks(a: &[f64], b: &[f64], t: f64) -> Vec<f64> {
+    a.iter().zip(b).map(|(a, b)| a + (b - a) * t).collect()
+}
+
+/// Move a smoothed period towards a target, returning it.
+///
+/// Unvoiced targets leave the period as it is, for the next voiced one.
+fn smooth_period(period: &mut f64, target: f64) -> f64 {
+    if target == 0.0 {
+        return 0.0;
+    }
+    if *period == 0.0 {
+        *period = target;
+    } else {
+        *period += (target - *period) * PERIOD_SMOOTHING;
+    }
+    *period
+}
+
+/// Scale a length in samples by the speaking rate.
+fn scale_len(len: usize, prosody: Prosody) -> usize {
+    ((len as f64 / prosody.rate).round() as usize).max(1)
+}
+
+/// Scale a length in ms by the speaking rate, giving samples.
+fn scale_ms(ms: f64, prosody: Prosody) -> usize {
+    scale_len((ms * SAMPLES_PER_MS as f64).round() as usize, prosody)
+}
+
+/// Parse a contour, with a time in seconds, F0 in Hz and optional volume on
+/// each line.
+///
+/// Blank lines and lines starting with `#` are ignored. Points must be in
+/// order of time, with a positive F0 and a volume of at least zero.
+pub fn parse_contour(s: &str) -> Result<Vec<ContourPoint>, ContourError> {
+    let mut result: Vec<ContourPoint> = vec![];
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = ContourError { line: i + 1 };
+        let fields = line
+            .split_ascii_whitespace()
+            .map(|x| x.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| err)?;
+        let point = match fields[..] {
+            [time, f0] => ContourPoint {
+                time,
+                f0,
+                volume: 1.0,
+            },
+            [time, f0, volume] => ContourPoint { time, f0, volume },
+            _ => return Err(err),
+        };
+        let valid = point.time.is_finite()
+            && point.f0.is_finite()
+            && point.f0 > 0.0
+            && point.volume.is_finite()
+            && point.volume >= 0.0;
+        if !valid || result.last().is_some_and(|last| last.time > point.time) {
+            return Err(err);
+        }
+        result.push(point);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contour() {
+        let points = parse_contour("# time f0 volume\n0 120\n\n0.5 100 0.5\n").unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(
+            (points[1].time, points[1].f0, points[1].volume),
+            (0.5, 100.0, 0.5)
+        );
+        for text in [
+            "0 NaN",
+            "0 0",
+            "0 -100",
+            "0 inf",
+            "NaN 100",
+            "0 100 NaN",
+            "0 100 -1",
+            "1 100\n0 100",
+            "0",
+        ] {
+            assert_eq!(
+                parse_contour(text).err().map(|e| e.line),
+                Some(text.lines().count())
+            );
         }
     }
 }
@@ -0,0 +1,203 @@
+//! Input as SP0256-AL2 allophone codes.
+//!
+//! The SP0256-AL2 speaks 64 allophones, five of which are pauses, and is
+//! driven by a sequence of their codes. Each allophone is given here either
+//! by its code, in decimal or as hex with a `0x` prefix, or by its mnemonic,
+//! as in `HH1 EH LL AX OW`. Allophones are mapped to the concise IPA used
+//! elsewhere, and spoken with their durations from the datasheet.
+
+use std::{fmt, ops::Range};
+
+use crate::utterance::{Item, Prosody};
+
+/// An allophone of the SP0256-AL2.
+pub struct Allophone {
+    pub mnemonic: &'static str,
+    /// The equivalent phonemes, or empty for a pause.
+    pub ipa: &'static str,
+    /// Duration in ms.
+    pub duration: u32,
+}
+
+const fn al(mnemonic: &'static str, ipa: &'static str, duration: u32) -> Allophone {
+    Allophone {
+        mnemonic,
+        ipa,
+        duration,
+    }
+}
+
+/// The allophones, indexed by code, with an example word for each.
+///
+/// The variants of a consonant depend on its position, which is mostly
+/// covered by the allophones of the LPC voice, and otherwise ignored.
+pub const ALLOPHONES: [Allophone; 64] = [
+    al("PA1", "", 10),
+    al("PA2", "", 30),
+    al("PA3", "", 50),
+    al("PA4", "", 100),
+    al("PA5", "", 200),
+    al("OY", "ɔɪ", 420),  // boy
+    al("AY", "aɪ", 260),  // sky
+    al("EH", "ɛ", 70),    // end
+    al("KK3", "kʰ", 120), // comb
+    al("PP", "pʰ", 210),  // pow
+    al("JH", "dʒ", 140),  // dodge
+    al("NN1", "n", 140),  // thin
+    al("IH", "ɪ", 70),    // sit
+    al("TT2", "tʰ", 140), // to
+    al("RR1", "ɹ", 170),  // rural
+    al("AX", "ə", 70),    // succeed
+    al("MM", "m", 180),   // milk
+    al("TT1", "t", 100),  // part
+    al("DH1", "ð", 290),  // they
+    al("IY", "i", 250),   // see
+    al("EY", "eɪ", 280),  // beige
+    al("DD1", "d", 70),   // could
+    al("UW1", "u", 100),  // to
+    al("AO", "ɔ", 100),   // aught
+    al("AA", "ɑ", 100),   // hot
+    al("YY2", "j", 180),  // yes
+    al("AE", "æ", 120),   // hat
+    al("HH1", "h", 130),  // he
+    al("BB1", "b", 80),   // business
+    al("TH", "θ", 180),   // thin
+    al("UH", "ʊ", 100),   // book
+    al("UW2", "u", 260),  // food
+    al("AW", "aʊ", 370),  // out
+    al("DD2", "d", 160),  // do
+    al("GG3", "g", 140),  // wig
+    al("VV", "v", 190),   // vest
+    al("GG1", "g", 80),   // got
+    al("SH", "ʃ", 160),   // ship
+    al("ZH", "ʒ", 190),   // azure
+    al("RR2", "ɹ", 120),  // brain
+    al("FF", "f", 150),   // food
+    al("KK2", "k", 190),  // sky
+    al("KK1", "kʰ", 160), // can't
+    al("ZZ", "z", 210),   // zoo
+    al("NG", "ŋ", 220),   // anchor
+    al("LL", "l", 110),   // lake
+    al("WW", "w", 180),   // wool
+    al("XR", "ɛɚ", 360),  // repair
+    al("WH", "ʍ", 200),   // whig
+    al("YY1", "j", 130),  // yes
+    al("CH", "tʃ", 190),  // church
+    al("ER1", "ɚ", 160),  // fir
+    al("ER2", "ɚ", 300),  // fir
+    al("OW", "oʊ", 240),  // beau
+    al("DH2", "ð", 240),  // they
+    al("SS", "s", 90),    // vest
+    al("NN2", "n", 190),  // no
+    al("HH2", "h", 180),  // hoe
+    al("OR", "ɔɚ", 330),  // store
+    al("AR", "ɑɚ", 290),  // alarm
+    al("YR", "iɚ", 350),  // clear
+    al("GG2", "g", 40),   // guest
+    al("EL", "əl", 190),  // saddle
+    al("BB2", "b", 50),   // business
+];
+
+/// An allophone which is neither a valid code nor a mnemonic.
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub token: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown SP0256 allophone {:?}", self.token)
+    }
+}
+
+fn code(token: &str) -> Option<usize> {
+    let code = match token.strip_prefix("0x").or(token.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok()?,
+        None if token.starts_with(|c: char| c.is_ascii_digit()) => token.parse().ok()?,
+        None => {
+            return ALLOPHONES
+                .iter()
+                .position(|al| al.mnemonic.eq_ignore_ascii_case(token));
+        }
+    };
+    (code < ALLOPHONES.len()).then_some(code)
+}
+
+/// Parse allophones separated by whitespace or commas, giving the code and
+/// byte range of each.
+pub fn parse(text: &str) -> Result<Vec<(usize, Range<usize>)>, ParseError> {
+    let mut result = vec![];
+    for token in text.split(|c: char| c.is_whitespace() || c == ',') {
+        if token.is_empty() {
+            continue;
+        }
+        let start = token.as_ptr() as usize - text.as_ptr() as usize;
+        let src = start..start + token.len();
+        let code = code(token).ok_or_else(|| ParseError {
+            token: token.to_string(),
+        })?;
+        result.push((code, src));
+    }
+    Ok(result)
+}
+
+/// Whether an allophone is a stop or affricate, most of which is closure.
+fn has_closure(ipa: &str) -> bool {
+    ipa.starts_with(['p', 't', 'k', 'b', 'd', 'g'])
+}
+
+/// Convert allophones into items, each of which is a word of its own.
+///
+/// The rate of each allophone is set so that it lasts for its duration,
+/// given the total inherent duration of its phonemes in ms. Stops and
+/// affricates instead keep their rate, as the back end renders their
+/// closure, and any time the allophone lasts beyond that is a pause before
+/// them.
+pub fn items(text: &str, inherent: impl Fn(&str) -> u32) -> Result<Vec<Item>, ParseError> {
+    let mut result = vec![];
+    for (code, src) in parse(text)? {
+        let al = &ALLOPHONES[code];
+        if al.ipa.is_empty() {
+            result.push(Item::Break(al.duration));
+            continue;
+        }
+        let inherent = inherent(al.ipa);
+        let mut prosody = Prosody::default();
+        if has_closure(al.ipa) {
+            if al.duration > inherent {
+                result.push(Item::Break(al.duration - inherent));
+            }
+        } else if inherent > 0 {
+            prosody.rate = inherent as f64 / al.duration as f64;
+        }
+        result.push(Item::Prosody(prosody));
+        result.push(Item::Word {
+            phonemes: al.ipa.to_string(),
+            src,
+        });
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaks(text: &str, inherent: u32) -> Vec<u32> {
+        (items(text, |_| inherent).unwrap().iter())
+            .filter_map(|item| match item {
+                Item::Break(ms) => Some(*ms),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stop_closure() {
+        // PP lasts 210 ms, of which the back end renders 90.
+        assert_eq!(breaks("PP", 90), [120]);
+        assert_eq!(breaks("PP", 210), []);
+        assert_eq!(breaks("PP", 300), []);
+        assert_eq!(breaks("PA2 AA", 100), [30]);
+    }
+}
@@ -0,0 +1,131 @@
+//! Spelling out text character by character.
+//!
+//! This is used for spelling mode (reading a word letter by letter) and for
+//! echoing single characters, as when a key is pressed. The output is the
+//! same concise IPA as produced by [`TextToPhoneme`], so it can be fed to
+//! the same back ends.
+//!
+//! [`TextToPhoneme`]: crate::text_to_phoneme::TextToPhoneme
+
+const LETTERS: [&str; 26] = [
+    "ɛɪ",      // a
+    "bi",      // b
+    "si",      // c
+    "di",      // d
+    "i",       // e
+    "ɛf",      // f
+    "dʒi",     // g
+    "ɛɪtʃ",    // h
+    "aɪ",      // i
+    "dʒɛɪ",    // j
+    "kɛɪ",     // k
+    "ɛl",      // l
+    "ɛm",      // m
+    "ɛn",      // n
+    "o",       // o
+    "pi",      // p
+    "kju",     // q
+    "ɑɹ",      // r
+    "ɛs",      // s
+    "ti",      // t
+    "ju",      // u
+    "vi",      // v
+    "dʌbəlju", // w
+    "ɛks",     // x
+    "waɪ",     // y
+    "zi",      // z
+];
+
+const DIGITS: [&str; 10] = [
+    "ziɹo", "wʌn", "tu", "θɹi", "fɔɹ", "faɪv", "sɪks", "sɛvən", "ɛɪt", "naɪn",
+];
+
+const PUNCTUATION: &[(char, &str)] = &[
+    (' ', "spɛɪs"),
+    ('\t', "tæb"),
+    ('\n', "nu laɪn"),
+    ('!', "bæŋ"),
+    ('"', "kwot"),
+    ('#', "nʌmbɚ"),
+    ('$', "dɑlɚ"),
+    ('%', "pɚsɛnt"),
+    ('&', "ænd"),
+    ('\'', "tɪk"),
+    ('(', "lɛft pɛɹən"),
+    (')', "ɹaɪt pɛɹən"),
+    ('*', "stɑɹ"),
+    ('+', "plʌs"),
+    (',', "kɑmə"),
+    ('-', "dæʃ"),
+    ('.', "dɑt"),
+    ('/', "slæʃ"),
+    (':', "kolən"),
+    (';', "sɛmikolən"),
+    ('<', "lɛs"),
+    ('=', "ikwəlz"),
+    ('>', "gɹɛɪtɚ"),
+    ('?', "kwɛstʃən"),
+    ('@', "æt"),
+    ('[', "lɛft bɹækɪt"),
+    ('\\', "bækslæʃ"),
+    (']', "ɹaɪt bɹækɪt"),
+    ('^', "kæɹət"),
+    ('_', "ʌndɚskɔɹ"),
+    ('`', "gɹæv"),
+    ('{', "lɛft bɹɛɪs"),
+    ('|', "bɑɹ"),
+    ('}', "ɹaɪt bɹɛɪs"),
+    ('~', "tɪldə"),
+];
+
+/// Spoken before an uppercase letter.
+const CAP_PREFIX: &str = "kæp ";
+
+/// Separator between spelled characters, a short pause.
+const SEPARATOR: &str = " , ";
+
+/// The name of a character, as phonemes.
+///
+/// This does not indicate capitalization; see [`echo_char`].
+pub fn char_name(c: char) -> Option<&'static str> {
+    let c = c.to_ascii_lowercase();
+    if c.is_ascii_lowercase() {
+        Some(LETTERS[(c as u8 - b'a') as usize])
+    } else if c.is_ascii_digit() {
+        Some(DIGITS[(c as u8 - b'0') as usize])
+    } else {
+        PUNCTUATION
+            .iter()
+            .find(|(p, _)| *p == c)
+            .map(|(_, name)| *name)
+    }
+}
+
+/// Phonemes for echoing a single character, for example a key press.
+///
+/// Uppercase letters are prefixed with "cap". Returns `None` for characters
+/// that have no name.
+pub fn echo_char(c: char) -> Option<String> {
+    let name = char_name(c)?;
+    if c.is_ascii_uppercase() {
+        Some(format!("{CAP_PREFIX}{name}"))
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// Spell out text, one character at a time.
+///
+/// Characters without a name are skipped.
+pub fn spell(text: &str) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        if let Some(phonemes) = echo_char(c) {
+            if !result.is_empty() {
+                result.push_str(SEPARATOR);
+            }
+            result.push_str(&phonemes);
+        }
+    }
+    result
+}
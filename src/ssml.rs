@@ -0,0 +1,594 @@
+//! A front end for a subset of SSML.
+//!
+//! The supported elements are `speak`, `p`, `s`, `break`, `prosody`,
+//! `emphasis`, `say-as`, `phoneme`, `sub` and `mark`. Unknown elements are
+//! ignored, but their content is spoken.
+//!
+//! The tokenizer only depends on `core` and borrows from the input, so it is
+//! suitable for no_std use. Conversion to [`Item`]s requires allocation.
+
+use core::{fmt, ops::Range};
+
+use crate::{
+    text_to_phoneme::TextToPhoneme,
+    utterance::{self, Item, Prosody},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Token<'a> {
+    /// Character data, with entities not yet decoded.
+    Text(&'a str),
+    /// A start tag, or an empty element tag if `empty` is set.
+    Start {
+        name: &'a str,
+        attrs: Attrs<'a>,
+        empty: bool,
+    },
+    End(&'a str),
+}
+
+/// The attributes of a start tag.
+#[derive(Clone, Copy, Debug)]
+pub struct Attrs<'a> {
+    s: &'a str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// A `<` without a matching `>`.
+    UnterminatedTag,
+    /// A malformed attribute.
+    BadAttribute,
+    /// An attribute value that could not be understood.
+    BadValue,
+}
+
+/// An iterator over the tokens of an SSML document.
+///
+/// Comments, processing instructions and declarations are skipped.
+pub struct Tokenizer<'a> {
+    s: &'a str,
+    len: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(s: &'a str) -> Self {
+        Self { s, len: s.len() }
+    }
+
+    /// Byte offset in the input of the next token.
+    pub fn offset(&self) -> usize {
+        self.len - self.s.len()
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.s.is_empty() {
+                return None;
+            }
+            if !self.s.starts_with('<') {
+                let end = self.s.find('<').unwrap_or(self.s.len());
+                let (text, rest) = self.s.split_at(end);
+                self.s = rest;
+                return Some(Ok(Token::Text(text)));
+            }
+            let terminator = if self.s.starts_with("<!--") {
+                "-->"
+            } else {
+                ">"
+            };
+            let Some(end) = self.s.find(terminator) else {
+                self.s = "";
+                return Some(Err(Error::UnterminatedTag));
+            };
+            let tag = &self.s[1..end];
+            self.s = &self.s[end + terminator.len()..];
+            if tag.starts_with('!') || tag.starts_with('?') {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                return Some(Ok(Token::End(name.trim())));
+            }
+            let (tag, empty) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+            let name_end = tag
+                .find(|c: char| c.is_ascii_whitespace())
+                .unwrap_or(tag.len());
+            let (name, attrs) = tag.split_at(name_end);
+            let attrs = Attrs { s: attrs };
+            return Some(Ok(Token::Start { name, attrs, empty }));
+        }
+    }
+}
+
+impl<'a> Attrs<'a> {
+    /// Get the raw value of an attribute, with entities not yet decoded.
+    pub fn get(&self, name: &str) -> Result<Option<&'a str>, Error> {
+        for attr in *self {
+            let (attr_name, value) = attr?;
+            if attr_name == name {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'a> Iterator for Attrs<'a> {
+    type Item = Result<(&'a str, &'a str), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let s = self.s.trim_start();
+        if s.is_empty() {
+            return None;
+        }
+        let Some((name, rest)) = s.split_once('=') else {
+            self.s = "";
+            return Some(Err(Error::BadAttribute));
+        };
+        let rest = rest.trim_start();
+        let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            self.s = "";
+            return Some(Err(Error::BadAttribute));
+        };
+        let Some((value, rest)) = rest[1..].split_once(quote) else {
+            self.s = "";
+            return Some(Err(Error::BadAttribute));
+        };
+        self.s = rest;
+        Some(Ok((name.trim(), value)))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnterminatedTag => write!(f, "unterminated tag"),
+            Error::BadAttribute => write!(f, "malformed attribute"),
+            Error::BadValue => write!(f, "invalid attribute value"),
+        }
+    }
+}
+
+/// An iterator over the characters of text, decoding the predefined XML
+/// entities and numeric character references.
+///
+/// Each character is reported along with its byte offset in the text.
+pub struct Entities<'a> {
+    s: &'a str,
+    len: usize,
+}
+
+impl<'a> Entities<'a> {
+    pub fn new(s: &'a str) -> Self {
+        Self { s, len: s.len() }
+    }
+}
+
+impl Iterator for Entities<'_> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.len - self.s.len();
+        let c = self.s.chars().next()?;
+        if c == '&'
+            && let Some(end) = self.s.find(';')
+            && let Some(decoded) = decode_entity(&self.s[1..end])
+        {
+            self.s = &self.s[end + 1..];
+            return Some((offset, decoded));
+        }
+        self.s = &self.s[c.len_utf8()..];
+        Some((offset, c))
+    }
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let num = entity.strip_prefix('#')?;
+            let code = match num.strip_prefix('x') {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => num.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// Decode the predefined XML entities and numeric character references.
+pub fn decode_entities(s: &str) -> String {
+    Entities::new(s).map(|(_, c)| c).collect()
+}
+
+/// Content handling for the element currently being processed.
+enum Content {
+    /// Speak the content as text.
+    Speak,
+    /// Discard the content.
+    Skip,
+    /// Replace the content with phonemes.
+    Phoneme(String),
+    /// Replace the content with text.
+    Sub(String),
+    /// Collect the content, for interpretation by `say-as`.
+    SayAs {
+        interpret_as: String,
+        format: Option<String>,
+        text: String,
+    },
+}
+
+struct Element {
+    name: String,
+    prosody: Prosody,
+    content: Content,
+    /// Byte offset of the start of the content.
+    start: usize,
+}
+
+/// Convert an SSML document to items.
+///
+/// Source ranges of words refer to the SSML text.
+pub fn parse(ttp: &TextToPhoneme, s: &str) -> Result<Vec<Item>, Error> {
+    let mut items = Vec::new();
+    let mut stack: Vec<Element> = Vec::new();
+    let mut prosody = Prosody::default();
+    let mut tokenizer = Tokenizer::new(s);
+    loop {
+        let offset = tokenizer.offset();
+        let Some(token) = tokenizer.next() else {
+            break;
+        };
+        match token? {
+            Token::Text(text) => match stack.last_mut().map(|el| &mut el.content) {
+                Some(Content::Speak) | None => {
+                    let chars = Entities::new(text).map(|(i, c)| (i + offset, c));
+                    utterance::from_chars(ttp, chars, offset + text.len(), true, &mut items);
+                }
+                Some(Content::SayAs {
+                    text: collected, ..
+                }) => collected.push_str(&decode_entities(text)),
+                _ => (),
+            },
+            Token::Start { name, attrs, empty } => {
+                let outer = prosody;
+                let content = start_element(name, attrs, &mut prosody, &mut items)?;
+                if prosody != outer {
+                    items.push(Item::Prosody(prosody));
+                }
+                let start = tokenizer.offset();
+                if empty {
+                    end_element(ttp, name, content, start..start, &mut items);
+                    if prosody != outer {
+                        prosody = outer;
+                        items.push(Item::Prosody(prosody));
+                    }
+                } else {
+                    let content = match stack.last().map(|el| &el.content) {
+                        Some(Content::Speak) | None => content,
+                        // Nested elements inside replaced content are ignored.
+                        _ => Content::Skip,
+                    };
+                    stack.push(Element {
+                        name: name.to_string(),
+                        prosody: outer,
+                        content,
+                        start,
+                    });
+                }
+            }
+            Token::End(name) => {
+                // Be lenient about mismatched tags, closing any open elements.
+                while let Some(el) = stack.pop() {
+                    end_element(ttp, &el.name, el.content, el.start..offset, &mut items);
+                    if el.prosody != prosody {
+                        prosody = el.prosody;
+                        items.push(Item::Prosody(prosody));
+                    }
+                    if el.name == name {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    Ok(items)
+}
+
+fn start_element(
+    name: &str,
+    attrs: Attrs,
+    prosody: &mut Prosody,
+    items: &mut Vec<Item>,
+) -> Result<Content, Error> {
+    match name {
+        "break" => {
+            let ms = if let Some(time) = attrs.get("time")? {
+                parse_time(time).ok_or(Error::BadValue)?
+            } else {
+                match attrs.get("strength")? {
+                    Some("none") => 0,
+                    Some("x-weak") => 50,
+                    Some("weak") => 100,
+                    Some("medium") | None => 200,
+                    Some("strong") => 400,
+                    Some("x-strong") => 800,
+                    Some(_) => return Err(Error::BadValue),
+                }
+            };
+            if ms > 0 {
+                items.push(Item::Break(ms));
+            }
+        }
+        "prosody" => {
+            let mut p = Prosody::default();
+            if let Some(rate) = attrs.get("rate")? {
+                p.rate = parse_rate(rate).ok_or(Error::BadValue)?;
+            }
+            if let Some(pitch) = attrs.get("pitch")? {
+                p.pitch = parse_pitch(pitch).ok_or(Error::BadValue)?;
+            }
+            if let Some(volume) = attrs.get("volume")? {
+                p.volume = parse_volume(volume).ok_or(Error::BadValue)?;
+            }
+            *prosody = prosody.then(p);
+        }
+        "emphasis" => {
+            let p = match attrs.get("level")? {
+                Some("strong") => Prosody {
+                    rate: 0.85,
+                    pitch: 1.1,
+                    volume: 1.4,
+                },
+                Some("moderate") | None => Prosody {
+                    rate: 0.9,
+                    pitch: 1.05,
+                    volume: 1.2,
+                },
+                Some("reduced") => Prosody {
+                    rate: 1.1,
+                    pitch: 0.95,
+                    volume: 0.8,
+                },
+                Some("none") => Prosody::default(),
+                Some(_) => return Err(Error::BadValue),
+            };
+            *prosody = prosody.then(p);
+        }
+        "say-as" => {
+            let interpret_as = attrs.get("interpret-as")?.unwrap_or_default();
+            let format = attrs.get("format")?.map(|f| f.to_string());
+            return Ok(Content::SayAs {
+                interpret_as: interpret_as.to_string(),
+                format,
+                text: String::new(),
+            });
+        }
+        "phoneme" => {
+            match attrs.get("alphabet")? {
+                Some("ipa") | None => (),
+                Some(_) => return Err(Error::BadValue),
+            }
+            let ph = attrs.get("ph")?.ok_or(Error::BadAttribute)?;
+            return Ok(Content::Phoneme(decode_entities(ph)));
+        }
+        "sub" => {
+            let alias = attrs.get("alias")?.ok_or(Error::BadAttribute)?;
+            return Ok(Content::Sub(decode_entities(alias)));
+        }
+        "mark" => {
+            let mark = attrs.get("name")?.ok_or(Error::BadAttribute)?;
+            items.push(Item::Mark(decode_entities(mark)));
+        }
+        _ => (),
+    }
+    Ok(Content::Speak)
+}
+
+fn end_element(
+    ttp: &TextToPhoneme,
+    name: &str,
+    content: Content,
+    src: Range<usize>,
+    items: &mut Vec<Item>,
+) {
+    match content {
+        Content::Phoneme(phonemes) => items.push(Item::Word { phonemes, src }),
+        Content::Sub(alias) => push_text(ttp, &alias, src, items),
+        Content::SayAs {
+            interpret_as,
+            format,
+            text,
+        } => say_as(
+            ttp,
+            &interpret_as,
+            format.as_deref(),
+            text.trim(),
+            src,
+            items,
+        ),
+        _ => (),
+    }
+    match name {
+        "p" => items.push(Item::Break(600)),
+        "s" => items.push(Item::Break(400)),
+        _ => (),
+    }
+}
+
+/// Convert text to items, attributing all words to the given source range.
+fn push_text(ttp: &TextToPhoneme, text: &str, src: Range<usize>, items: &mut Vec<Item>) {
+    let start = items.len();
+    utterance::from_text(ttp, text, 0, true, items);
+    for item in &mut items[start..] {
+        if let Item::Word { src: word_src, .. } = item {
+            *word_src = src.clone();
+        }
+    }
+}
+
+fn say_as(
+    ttp: &TextToPhoneme,
+    interpret_as: &str,
+    format: Option<&str>,
+    text: &str,
+    src: Range<usize>,
+    items: &mut Vec<Item>,
+) {
+    match interpret_as {
+        "characters" | "spell-out" => items.push(Item::Word {
+            phonemes: crate::spell::spell(text),
+            src,
+        }),
+        "cardinal" | "number" => {
+            let digits = text.replace(',', "");
+            match digits.parse::<u64>() {
+                Ok(n) => push_text(ttp, &crate::numbers::cardinal(n), src, items),
+                Err(_) => push_text(ttp, text, src, items),
+            }
+        }
+        "ordinal" => match text.parse::<u64>() {
+            Ok(n) => push_text(ttp, &crate::numbers::ordinal(n), src, items),
+            Err(_) => push_text(ttp, text, src, items),
+        },
+        "date" => match date_words(text, format.unwrap_or("mdy")) {
+            Some(words) => push_text(ttp, &words, src, items),
+            None => push_text(ttp, text, src, items),
+        },
+        _ => push_text(ttp, text, src, items),
+    }
+}
+
+/// Expand a date into words.
+///
+/// The format is a permutation of some of the letters `y`, `m` and `d`, as in
+/// the W3C say-as note, and the fields are separated by `-`, `/` or `.`.
+fn date_words(text: &str, format: &str) -> Option<String> {
+    let mut fields = text.split(['-', '/', '.']);
+    let (mut year, mut month, mut day) = (None, None, None);
+    for f in format.chars() {
+        let value = fields.next()?.trim().parse::<u64>().ok()?;
+        match f {
+            'y' => year = Some(value),
+            'm' => month = Some(crate::numbers::month(value)?),
+            'd' => day = Some(value),
+            _ => return None,
+        }
+    }
+    if fields.next().is_some() {
+        return None;
+    }
+    let mut words = Vec::new();
+    if let Some(month) = month {
+        words.push(month.to_string());
+    }
+    if let Some(day) = day {
+        words.push(crate::numbers::ordinal(day));
+    }
+    if let Some(year) = year {
+        words.push(crate::numbers::year(year));
+    }
+    Some(words.join(" "))
+}
+
+/// Parse a duration such as "250ms" or "1.5s", returning ms.
+///
+/// Negative and non-finite durations are invalid, and longer ones than
+/// [`utterance::MAX_BREAK`] are clamped to it.
+pub fn parse_time(s: &str) -> Option<u32> {
+    let ms = if let Some(ms) = s.strip_suffix("ms") {
+        ms.trim().parse::<f64>().ok()?
+    } else {
+        s.strip_suffix('s')?.trim().parse::<f64>().ok()? * 1000.0
+    };
+    (ms.is_finite() && ms >= 0.0).then(|| ms.min(utterance::MAX_BREAK as f64) as u32)
+}
+
+/// Parse a relative change such as "+10%" or "-20%" as a multiplier.
+fn parse_percent(s: &str) -> Option<f64> {
+    let pct = s.strip_suffix('%')?.trim().parse::<f64>().ok()?;
+    if s.starts_with(['+', '-']) {
+        Some(1.0 + pct * 0.01)
+    } else {
+        Some(pct * 0.01)
+    }
+}
+
+pub fn parse_rate(s: &str) -> Option<f64> {
+    match s {
+        "x-slow" => Some(0.5),
+        "slow" => Some(0.75),
+        "medium" | "default" => Some(1.0),
+        "fast" => Some(1.33),
+        "x-fast" => Some(1.75),
+        _ => parse_percent(s).or_else(|| s.parse().ok()),
+    }
+    .filter(|rate| rate.is_finite() && *rate > 0.0)
+}
+
+pub fn parse_pitch(s: &str) -> Option<f64> {
+    match s {
+        "x-low" => Some(0.7),
+        "low" => Some(0.85),
+        "medium" | "default" => Some(1.0),
+        "high" => Some(1.2),
+        "x-high" => Some(1.4),
+        _ => {
+            if let Some(st) = s.strip_suffix("st") {
+                let st = st.trim().parse::<f64>().ok()?;
+                Some((st / 12.0).exp2())
+            } else {
+                parse_percent(s)
+            }
+        }
+    }
+    .filter(|pitch| pitch.is_finite() && *pitch > 0.0)
+}
+
+pub fn parse_volume(s: &str) -> Option<f64> {
+    match s {
+        "silent" => Some(0.0),
+        "x-soft" => Some(0.25),
+        "soft" => Some(0.5),
+        "medium" | "default" => Some(1.0),
+        "loud" => Some(1.5),
+        "x-loud" => Some(2.0),
+        _ => {
+            if let Some(db) = s.strip_suffix("dB") {
+                let db = db.trim().parse::<f64>().ok()?;
+                Some(10f64.powf(db / 20.0))
+            } else {
+                parse_percent(s)
+            }
+        }
+    }
+    .filter(|volume| volume.is_finite() && *volume >= 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("250ms"), Some(250));
+        assert_eq!(parse_time("1.5s"), Some(1500));
+        assert_eq!(parse_time("300000s"), Some(utterance::MAX_BREAK));
+        assert_eq!(parse_time("1e300ms"), Some(utterance::MAX_BREAK));
+        for s in ["-1s", "-5ms", "NaNs", "infms", "250", "fast"] {
+            assert_eq!(parse_time(s), None, "{s}");
+        }
+    }
+}
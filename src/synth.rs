@@ -1,45 +1,145 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tms5220;
+
 const EMPH: f64 = 0.9375;
 
+/// Energy of each impulse pulse, which other pulses are scaled to match.
+const PULSE_ENERGY: f64 = 1.0 / (EMPH * EMPH * (1.0 - EMPH * EMPH));
+
+/// Opening and closing times of a Rosenberg pulse, as fractions of the period.
+const ROSENBERG_OPEN: f64 = 0.4;
+const ROSENBERG_CLOSE: f64 = 0.16;
+
+/// Pole of the filter tilting noise, so that it falls off with frequency.
+const NOISE_TILT: f64 = 0.5;
+
+/// The shape of each pulse exciting voiced frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pulse {
+    /// An impulse, decaying to undo the pre-emphasis of the analysis.
+    #[default]
+    Impulse,
+    /// The chirp of the TMS5220.
+    Chirp,
+    /// The derivative of a Rosenberg glottal pulse.
+    Rosenberg,
+}
+
+/// The spectrum of the noise exciting unvoiced frames and mixed into voiced
+/// ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Noise {
+    #[default]
+    White,
+    /// Falling off with frequency.
+    Tilted,
+}
+
+/// The sources exciting the filter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Excitation {
+    #[serde(default)]
+    pub pulse: Pulse,
+    #[serde(default)]
+    pub noise: Noise,
+}
+
 pub struct Synth {
     y: f64,
     x: Vec<f64>,
-    phase: u16,
+    /// Samples until the next glottal pulse.
+    phase: f64,
+    /// Period at the last glottal pulse.
+    pulse_period: f64,
     rand: u16,
+    /// State of the filter tilting noise.
+    tilt: f64,
+    /// Gate the noise of voiced frames with the pitch period.
+    modulated_noise: bool,
+    excitation: Excitation,
+    /// Steps through the chirp per sample.
+    chirp_step: f64,
+    /// Gain of the chirp, to match the energy of an impulse pulse.
+    chirp_gain: f64,
 }
 
+#[derive(Clone)]
 pub struct Params {
     pub k: Vec<f64>,
-    pub period: u16,
+    /// Pitch period in samples, which need not be whole, or 0 if unvoiced.
+    pub period: f64,
+    /// Gain of the pulse train, or of the noise if unvoiced.
     pub rms: f64,
+    /// Gain of noise mixed with the pulse train, if voiced.
+    pub noise: f64,
 }
 
+/// Fraction of the pitch period after each pulse in which modulated noise is
+/// on, roughly the open phase of the glottis.
+const NOISE_DUTY: f64 = 0.5;
+
 impl Synth {
     pub fn new(n: usize) -> Self {
         Self {
             y: 0.0,
             x: vec![0.0; n + 1],
-            phase: 0,
+            phase: 0.0,
+            pulse_period: 0.0,
             rand: 1,
+            tilt: 0.0,
+            modulated_noise: false,
+            excitation: Excitation::default(),
+            chirp_step: 1.0,
+            chirp_gain: 1.0,
         }
     }
 
+    /// Choose the excitation, where the chirp is resampled from the chip's
+    /// sample rate to `sample_rate`.
+    pub fn set_excitation(&mut self, excitation: Excitation, sample_rate: f64) {
+        self.excitation = excitation;
+        self.chirp_step = tms5220::SAMPLE_RATE as f64 / sample_rate;
+        let energy = (tms5220::CHIRP.iter())
+            .map(|&c| (c as f64 / 128.).powi(2))
+            .sum::<f64>()
+            / self.chirp_step;
+        self.chirp_gain = (PULSE_ENERGY / energy).sqrt();
+    }
+
+    /// Gate the noise of voiced frames so that it is only on for part of
+    /// each pitch period, as in natural voiced fricatives.
+    pub fn set_modulated_noise(&mut self, modulated_noise: bool) {
+        self.modulated_noise = modulated_noise;
+    }
+
     pub fn get_sample(&mut self, params: &Params) -> f64 {
         let mut u;
-        if params.period > 0 {
-            if self.phase == 0 {
+        if params.period > 0.0 {
+            if self.phase <= 0.0 {
                 self.y += 1.0 / EMPH;
+                self.phase += params.period;
+                self.pulse_period = params.period;
             }
-            u = self.y;
+            u = self.pulse(self.pulse_period - self.phase) * params.rms;
             self.y *= EMPH;
-            self.phase += 1;
-            if self.phase >= params.period {
-                self.phase = 0;
+            if params.noise > 0.0 {
+                let mut noise = params.noise * self.noise();
+                if self.modulated_noise {
+                    // Keep the same power on average.
+                    let open = self.phase > params.period * (1.0 - NOISE_DUTY);
+                    noise *= if open { NOISE_DUTY.recip().sqrt() } else { 0.0 };
+                }
+                u += noise;
             }
+            self.phase -= 1.0;
         } else {
-            self.rand = (self.rand >> 1) ^ if (self.rand & 1) != 0 { 0xb800 } else { 0 };
-            u = if (self.rand & 1) != 0 { 1.0 } else { -1.0 };
+            u = self.noise() * params.rms;
         }
-        u *= params.rms;
         let n = params.k.len();
         for i in (0..n).rev() {
             u -= params.k[i] * self.x[i];
@@ -48,6 +148,44 @@ impl Synth {
         self.x[0] = u;
         u
     }
+
+    /// The pulse exciting voiced frames, `t` samples after it starts.
+    fn pulse(&self, t: f64) -> f64 {
+        match self.excitation.pulse {
+            Pulse::Impulse => self.y,
+            Pulse::Chirp => {
+                let ix = (t * self.chirp_step) as usize;
+                let c = tms5220::CHIRP.get(ix).map_or(0.0, |&c| c as f64 / 128.);
+                c * self.chirp_gain
+            }
+            Pulse::Rosenberg => {
+                let open = ROSENBERG_OPEN * self.pulse_period;
+                let close = ROSENBERG_CLOSE * self.pulse_period;
+                let energy = PI * PI / 8.0 * (open.recip() + close.recip());
+                let y = if t < open {
+                    PI / (2.0 * open) * (PI * t / open).sin()
+                } else if t < open + close {
+                    -PI / (2.0 * close) * (PI * (t - open) / (2.0 * close)).sin()
+                } else {
+                    0.0
+                };
+                y * (PULSE_ENERGY / energy).sqrt()
+            }
+        }
+    }
+
+    /// Noise of unit power, from an LFSR.
+    fn noise(&mut self) -> f64 {
+        self.rand = (self.rand >> 1) ^ if (self.rand & 1) != 0 { 0xb800 } else { 0 };
+        let white = if (self.rand & 1) != 0 { 1.0 } else { -1.0 };
+        match self.excitation.noise {
+            Noise::White => white,
+            Noise::Tilted => {
+                self.tilt = NOISE_TILT * self.tilt + (1.0 - NOISE_TILT * NOISE_TILT).sqrt() * white;
+                self.tilt
+            }
+        }
+    }
 }
 
 impl Params {
@@ -60,8 +198,9 @@ impl Params {
                 .zip(&other.k)
                 .map(|(a, b)| a * mt + b * t)
                 .collect(),
-            period: (self.period as f64 * mt + other.period as f64 * t) as u16,
-            rms: self.rms as f64 * mt + other.rms * t,
+            period: self.period * mt + other.period * t,
+            rms: self.rms * mt + other.rms * t,
+            noise: self.noise * mt + other.noise * t,
         }
     }
 }
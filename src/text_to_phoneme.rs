@@ -474,7 +474,7 @@ impl TextToPhoneme {
         let mut result = String::new();
         let mut ix = 1;
         while ix < text.len() {
-            if let Some(pos) = text[ix + 1..].as_bytes().iter().position(|c| *c == b' ') {
+            if let Some(pos) = text.as_bytes()[ix + 1..].iter().position(|c| *c == b' ') {
                 if !result.is_empty() {
                     result.push(' ');
                 }
@@ -0,0 +1,319 @@
+//! Encoding and decoding of TMS5220 bitstreams.
+//!
+//! The TMS5220 plays 25 ms frames at 8 kHz, each with a 4 bit energy, a
+//! repeat flag, a 6 bit pitch and ten reflection coefficients, for a bit rate
+//! of at most 2 kbit/s. Unvoiced frames only carry the first four
+//! coefficients, and repeated frames none. The coefficient tables are those
+//! of the chip, as used by the MAME emulation.
+//!
+//! Bits are packed in the same order as the speech ROMs, so that each field
+//! is read most significant bit first, starting from the least significant
+//! bit of each byte.
+
+use crate::{synth::Params, vocoder};
+
+pub const SAMPLE_RATE: u32 = 8_000;
+/// Samples per frame.
+pub const FRAME_LEN: usize = 200;
+/// Length of the analysis window, in samples.
+const WINDOW: usize = 400;
+
+const ENERGY: [u16; 16] = [0, 1, 2, 3, 4, 6, 8, 11, 16, 23, 33, 47, 63, 85, 114, 0];
+/// Energy code of a frame that ends the stream.
+const ENERGY_STOP: u8 = 15;
+/// Scale from energy table units to the gain of [`Params`].
+const ENERGY_SCALE: f64 = 50.0;
+
+const PITCH: [u8; 64] = [
+    0, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37,
+    38, 39, 40, 41, 42, 44, 46, 48, 50, 52, 53, 56, 58, 60, 62, 65, 68, 70, 72, 76, 78, 80, 84, 86,
+    91, 94, 98, 101, 105, 109, 114, 118, 122, 127, 132, 137, 142, 148, 153, 159,
+];
+
+/// The excitation of voiced frames, scaled by 128, at the chip's sample rate.
+pub const CHIRP: [i8; 41] = [
+    0, 42, -44, 50, -78, 18, 37, 20, 2, -31, -59, 2, 95, 90, 5, 15, 38, -4, -91, -91, -42, -35,
+    -36, -4, 37, 43, 34, 33, 15, -1, -8, -18, -19, -17, -9, -10, -6, 0, 3, 2, 1,
+];
+
+/// Reflection coefficients, scaled by 512.
+const K1: [i16; 32] = [
+    -501, -498, -497, -495, -493, -491, -488, -482, -478, -474, -469, -464, -459, -452, -445, -437,
+    -412, -380, -339, -288, -227, -158, -81, -1, 80, 157, 226, 287, 337, 379, 411, 436,
+];
+const K2: [i16; 32] = [
+    -328, -303, -274, -244, -211, -175, -138, -99, -61, -22, 17, 56, 94, 133, 171, 207, 243, 273,
+    302, 326, 347, 366, 381, 395, 407, 418, 427, 435, 442, 448, 453, 462,
+];
+const K3: [i16; 16] = [
+    -441, -387, -333, -279, -225, -171, -117, -63, -9, 45, 98, 152, 206, 260, 314, 368,
+];
+const K4: [i16; 16] = [
+    -328, -273, -217, -161, -106, -50, 5, 61, 116, 172, 228, 283, 339, 394, 450, 506,
+];
+const K5: [i16; 16] = [
+    -328, -282, -235, -189, -142, -96, -50, -3, 43, 90, 136, 182, 229, 275, 322, 368,
+];
+const K6: [i16; 16] = [
+    -256, -212, -168, -123, -79, -35, 10, 54, 98, 143, 187, 232, 276, 320, 365, 409,
+];
+const K7: [i16; 16] = [
+    -308, -260, -212, -164, -117, -69, -21, 27, 75, 122, 170, 218, 266, 314, 361, 409,
+];
+const K8: [i16; 8] = [-256, -161, -66, 29, 124, 219, 314, 409];
+const K9: [i16; 8] = [-256, -176, -96, -15, 65, 146, 226, 307];
+const K10: [i16; 8] = [-205, -132, -59, 14, 87, 160, 234, 307];
+
+const K_TABLES: [&[i16]; 10] = [&K1, &K2, &K3, &K4, &K5, &K6, &K7, &K8, &K9, &K10];
+/// Number of coefficients in an unvoiced frame.
+const N_UNVOICED_K: usize = 4;
+
+/// The pitch and coefficients of a frame, as table indices.
+#[derive(Clone, Copy)]
+struct Frame {
+    pitch: u8,
+    k: [u8; 10],
+}
+
+/// Index of the table entry nearest to a value.
+fn quantize<T: Copy + Into<f64>>(table: &[T], x: f64) -> u8 {
+    let dist = |i: usize| (table[i].into() - x).abs();
+    (0..table.len())
+        .min_by(|a, b| dist(*a).total_cmp(&dist(*b)))
+        .unwrap() as u8
+}
+
+/// Halve the sample rate, with a windowed sinc low-pass filter.
+pub fn decimate(samples: &[f64]) -> Vec<f64> {
+    const HALF_LEN: isize = 16;
+    let taps = (-HALF_LEN..=HALF_LEN)
+        .map(|n| {
+            let x = n as f64 * 0.5 * std::f64::consts::PI;
+            let sinc = if n == 0 { 1.0 } else { x.sin() / x };
+            let window = 0.5 + 0.5 * (n as f64 * std::f64::consts::PI / HALF_LEN as f64).cos();
+            0.5 * sinc * window
+        })
+        .collect::<Vec<_>>();
+    (0..samples.len() / 2)
+        .map(|i| {
+            let center = 2 * i as isize;
+            taps.iter()
+                .enumerate()
+                .map(|(j, h)| {
+                    let ix = center + j as isize - HALF_LEN;
+                    h * samples.get(ix as usize).copied().unwrap_or_default()
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// Encode a recording at 8 kHz as a bitstream.
+pub fn encode(samples: &[f64], preemph: f64) -> Vec<u8> {
+    let params = vocoder::analyze(samples, SAMPLE_RATE, preemph, FRAME_LEN, WINDOW);
+    let mut w = BitWriter::default();
+    let mut prev: Option<Frame> = None;
+    for p in params {
+        let energy = quantize(&ENERGY[..ENERGY_STOP as usize], p.rms / ENERGY_SCALE);
+        if energy == 0 {
+            w.write(0, 4);
+            prev = None;
+            continue;
+        }
+        let pitch = if p.period == 0.0 {
+            0
+        } else {
+            1 + quantize(&PITCH[1..], p.period)
+        };
+        let mut k = [0; 10];
+        let n_k = if pitch == 0 { N_UNVOICED_K } else { k.len() };
+        for i in 0..n_k {
+            k[i] = quantize(K_TABLES[i], p.k[i] * 512.);
+        }
+        w.write(energy as u32, 4);
+        let repeat = prev.is_some_and(|prev| (prev.pitch == 0) == (pitch == 0) && prev.k == k);
+        w.write(repeat as u32, 1);
+        w.write(pitch as u32, 6);
+        if !repeat {
+            for (i, &ix) in k[..n_k].iter().enumerate() {
+                w.write(ix as u32, K_TABLES[i].len().ilog2());
+            }
+        }
+        prev = Some(Frame { pitch, k });
+    }
+    w.write(ENERGY_STOP as u32, 4);
+    w.finish()
+}
+
+/// Decode a bitstream into frames of parameters.
+///
+/// Decoding stops at a stop frame or the end of the data.
+pub fn decode(data: &[u8]) -> Vec<Params> {
+    let mut r = BitReader { data, pos: 0 };
+    let mut result = vec![];
+    let mut k = [0.0; 10];
+    while let Some(energy) = r.read(4) {
+        if energy == ENERGY_STOP as u32 {
+            break;
+        }
+        if energy == 0 {
+            result.push(Params {
+                k: k.to_vec(),
+                period: 0.0,
+                rms: 0.0,
+                noise: 0.0,
+            });
+            continue;
+        }
+        let (Some(repeat), Some(pitch)) = (r.read(1), r.read(6)) else {
+            break;
+        };
+        if repeat == 0 {
+            let n_k = if pitch == 0 { N_UNVOICED_K } else { k.len() };
+            k = [0.0; 10];
+            for (i, k) in k[..n_k].iter_mut().enumerate() {
+                let table = K_TABLES[i];
+                let Some(ix) = r.read(table.len().ilog2()) else {
+                    return result;
+                };
+                *k = table[ix as usize] as f64 / 512.;
+            }
+        }
+        result.push(Params {
+            k: k.to_vec(),
+            period: PITCH[pitch as usize] as f64,
+            rms: ENERGY[energy as usize] as f64 * ENERGY_SCALE,
+            noise: 0.0,
+        });
+    }
+    result
+}
+
+#[derive(Default)]
+struct BitWriter {
+    data: Vec<u8>,
+    n_bits: usize,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, n_bits: u32) {
+        for i in (0..n_bits).rev() {
+            if self.n_bits.is_multiple_of(8) {
+                self.data.push(0);
+            }
+            if (value >> i) & 1 != 0 {
+                *self.data.last_mut().unwrap() |= 1 << (self.n_bits % 8);
+            }
+            self.n_bits += 1;
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, n_bits: u32) -> Option<u32> {
+        let mut value = 0;
+        for _ in 0..n_bits {
+            let byte = self.data.get(self.pos / 8)?;
+            value = (value << 1) | ((byte >> (self.pos % 8)) & 1) as u32;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A vowel-like signal: a 100 Hz pulse train through a 700 Hz resonance,
+    /// preceded by a short silence.
+    fn vowel() -> Vec<f64> {
+        let (r, theta) = (
+            0.95_f64,
+            2.0 * std::f64::consts::PI * 700.0 / SAMPLE_RATE as f64,
+        );
+        let (a1, a2) = (2.0 * r * theta.cos(), -r * r);
+        let (mut y1, mut y2) = (0.0, 0.0);
+        (0..SAMPLE_RATE as usize)
+            .map(|i| {
+                let x = if i >= 2 * WINDOW && i % 80 == 0 {
+                    2000.0
+                } else {
+                    0.0
+                };
+                let y = x + a1 * y1 + a2 * y2;
+                (y2, y1) = (y1, y);
+                y
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bits_round_trip() {
+        let fields = [(0b1011, 4), (1, 1), (0b100101, 6), (0, 3), (0b11111, 5)];
+        let mut w = BitWriter::default();
+        for (value, n_bits) in fields {
+            w.write(value, n_bits);
+        }
+        let data = w.finish();
+        assert_eq!(data.len(), 3);
+        let mut r = BitReader {
+            data: &data,
+            pos: 0,
+        };
+        for (value, n_bits) in fields {
+            assert_eq!(r.read(n_bits), Some(value));
+        }
+    }
+
+    #[test]
+    fn decode_gives_quantized_analysis() {
+        let samples = vowel();
+        let analyzed = vocoder::analyze(&samples, SAMPLE_RATE, 0.9375, FRAME_LEN, WINDOW);
+        let decoded = decode(&encode(&samples, 0.9375));
+        assert_eq!(decoded.len(), analyzed.len());
+        assert!(decoded.iter().any(|p| p.rms == 0.0));
+        assert!(decoded.iter().any(|p| p.period > 0.0));
+        for (a, d) in analyzed.iter().zip(&decoded) {
+            let energy = quantize(&ENERGY[..ENERGY_STOP as usize], a.rms / ENERGY_SCALE);
+            assert_eq!(d.rms, ENERGY[energy as usize] as f64 * ENERGY_SCALE);
+            if energy == 0 {
+                continue;
+            }
+            if a.period == 0.0 {
+                assert_eq!(d.period, 0.0);
+            } else {
+                assert_eq!(
+                    d.period,
+                    PITCH[1 + quantize(&PITCH[1..], a.period) as usize] as f64
+                );
+            }
+            let n_k = if a.period == 0.0 { N_UNVOICED_K } else { 10 };
+            for (i, table) in K_TABLES.iter().enumerate() {
+                let expected = if i < n_k {
+                    table[quantize(table, a.k[i] * 512.) as usize] as f64 / 512.
+                } else {
+                    0.0
+                };
+                assert_eq!(d.k[i], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_stops_at_stop_frame() {
+        let mut data = encode(&vowel(), 0.9375);
+        let n_frames = decode(&data).len();
+        data.extend_from_slice(&[0xff; 4]);
+        assert_eq!(decode(&data).len(), n_frames);
+    }
+}
@@ -0,0 +1,126 @@
+//! Transitions between LPC phonemes.
+//!
+//! A transition blends the filter of one phoneme into the next, starting
+//! some time before the end of the first and continuing some way into the
+//! second, so the attack of the second is skipped. Without a transition, the first phoneme
+//! plays out its envelope and the next starts from silence.
+//!
+//! Transitions are chosen by rules, which match each phoneme either by name,
+//! by kind (such as `vowel`), or with `*` for any phoneme. The first matching
+//! rule is used.
+
+use serde::{Deserialize, Serialize};
+
+use crate::phonemes::{Phoneme, matches};
+
+/// How the blend progresses over time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    #[default]
+    Linear,
+    /// Slow at the ends and fast in the middle.
+    Smooth,
+}
+
+/// The timing of a transition, with times in ms.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Transition {
+    /// Time before the end of the first phoneme at which the blend starts.
+    pub lead: f64,
+    /// Length of the blend.
+    pub duration: f64,
+    /// Time into the second phoneme at which it continues after the blend.
+    pub next_start: f64,
+    /// Fraction of the blend over which the spectrum changes, centered in
+    /// it. A value of zero disables the transition.
+    pub overlap: f64,
+    #[serde(default)]
+    pub shape: Shape,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rule {
+    pub from: String,
+    pub to: String,
+    #[serde(flatten)]
+    pub transition: Transition,
+}
+
+const fn linear(overlap: f64) -> Transition {
+    Transition {
+        lead: 93.75,
+        duration: 93.75,
+        next_start: 37.5,
+        overlap,
+        shape: Shape::Linear,
+    }
+}
+
+/// A short transition from the end of a consonant into a vowel.
+const fn onset(lead: f64) -> Transition {
+    Transition {
+        lead,
+        duration: 30.0,
+        next_start: 37.5,
+        overlap: 1.0,
+        shape: Shape::Smooth,
+    }
+}
+
+/// The rules used when a voice doesn't have its own.
+const BUILTIN: &[(&str, &str, Transition)] = &[
+    ("vowel", "vowel", linear(1.0)),
+    ("nasal", "vowel", linear(0.2)),
+    ("vowel", "nasal", linear(0.05)),
+    ("nasal", "nasal", linear(0.2)),
+    // These start at the end of the steady part of each envelope.
+    ("fricative", "vowel", onset(31.25)),
+    // Plosives with a release move into the vowel by themselves, so this is
+    // only for those without.
+    ("plosive", "vowel", onset(20.0)),
+    ("affricate", "vowel", onset(30.0)),
+];
+
+pub fn builtin() -> Vec<Rule> {
+    BUILTIN
+        .iter()
+        .map(|(from, to, transition)| Rule {
+            from: from.to_string(),
+            to: to.to_string(),
+            transition: *transition,
+        })
+        .collect()
+}
+
+/// Find the transition between two phonemes, given with their names.
+pub fn find<'r>(
+    rules: &'r [Rule],
+    from: (&str, &Phoneme),
+    to: (&str, &Phoneme),
+) -> Option<&'r Transition> {
+    rules
+        .iter()
+        .find(|rule| matches(&rule.from, from.0, from.1) && matches(&rule.to, to.0, to.1))
+        .map(|rule| &rule.transition)
+        .filter(|transition| transition.overlap > 0.0)
+}
+
+/// Find a transition among the built-in rules, matching by kind only.
+pub fn find_builtin(from: &Phoneme, to: &Phoneme) -> Option<&'static Transition> {
+    BUILTIN
+        .iter()
+        .find(|(a, b, _)| matches(a, "", from) && matches(b, "", to))
+        .map(|(_, _, transition)| transition)
+}
+
+impl Transition {
+    /// The weight of the second phoneme, at a fraction `t` of the blend.
+    pub fn weight(&self, t: f64) -> f64 {
+        let t = (0.5 + (t - 0.5) / self.overlap).clamp(0., 1.);
+        match self.shape {
+            Shape::Linear => t,
+            Shape::Smooth => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Intermediate representation of an utterance.
+//!
+//! Front ends (plain text, SSML) produce a sequence of [`Item`]s, which are
+//! then lowered to [`Segment`]s for a particular back end. Phonemes are
+//! represented as concise IPA strings, as produced by [`TextToPhoneme`],
+//! until they are lowered.
+//!
+//! [`TextToPhoneme`]: crate::text_to_phoneme::TextToPhoneme
+
+use std::ops::Range;
+
+use crate::text_to_phoneme::TextToPhoneme;
+
+/// Prosodic modification, relative to the defaults of the voice.
+///
+/// All values are multipliers, so the default is 1.0 for each.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Prosody {
+    /// Speaking rate; larger values are faster.
+    pub rate: f64,
+    /// Fundamental frequency.
+    pub pitch: f64,
+    /// Amplitude.
+    pub volume: f64,
+}
+
+impl Default for Prosody {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            pitch: 1.0,
+            volume: 1.0,
+        }
+    }
+}
+
+impl Prosody {
+    /// Combine two modifications, as when nesting.
+    pub fn then(self, other: Prosody) -> Prosody {
+        Prosody {
+            rate: self.rate * other.rate,
+            pitch: self.pitch * other.pitch,
+            volume: self.volume * other.volume,
+        }
+    }
+}
+
+/// F1 in Hz at which [`f1_pitch`] leaves the pitch unchanged.
+const F1_PITCH_REF: f64 = 500.0;
+/// Strength of the inverse relation between F1 and pitch.
+const F1_PITCH_EXPONENT: f64 = 0.15;
+
+/// Pitch multiplier for a vowel with the given F1, in Hz.
+///
+/// As in Software Automatic Mouth, the pitch of a vowel varies inversely
+/// with its F1, so close vowels are a little higher than open ones, as in
+/// natural speech. This gives some variation at almost no cost.
+pub fn f1_pitch(f1: f64) -> f64 {
+    (F1_PITCH_REF / f1).powf(F1_PITCH_EXPONENT)
+}
+
+pub enum Item {
+    /// The phonemes of one word, and its byte range in the source text.
+    Word { phonemes: String, src: Range<usize> },
+    /// A pause, in ms.
+    Break(u32),
+    /// Set the prosody for the following words.
+    Prosody(Prosody),
+    /// A named point in the utterance.
+    Mark(String),
+}
+
+/// An element of the input to a synthesis back end.
+///
+/// The phone type depends on the back end.
+#[derive(Clone)]
+pub enum Segment<P> {
+    Phone(P, Prosody),
+    /// Silence, in ms.
+    Silence(u32),
+    /// An event to be reported when playback reaches this point.
+    Event(Event<P>),
+}
+
+/// A timed event reported by a back end alongside the audio.
+///
+/// Word and mark events are passed through from the input; phone events are
+/// generated by the back end at the start of each phone.
+#[derive(Clone, Debug)]
+pub enum Event<P> {
+    WordStart(Range<usize>),
+    WordEnd(Range<usize>),
+    Phone(P),
+    Mark(String),
+}
+
+/// Pause after a clause, in ms.
+const CLAUSE_BREAK: u32 = 200;
+/// Pause after a sentence, in ms.
+const SENTENCE_BREAK: u32 = 400;
+/// The longest pause, in ms. SSML sets no limit, but engines commonly cap
+/// `<break>` at ten seconds.
+pub const MAX_BREAK: u32 = 10_000;
+
+/// Convert plain text into items.
+///
+/// The text is split into words, which are lowercased and translated. If
+/// `normalize` is set, numbers are read as cardinals and punctuation becomes
+/// a pause. Source ranges are reported relative to `offset`.
+pub fn from_text(
+    ttp: &TextToPhoneme,
+    text: &str,
+    offset: usize,
+    normalize: bool,
+    items: &mut Vec<Item>,
+) {
+    let chars = text.char_indices().map(|(i, c)| (i + offset, c));
+    from_chars(ttp, chars, offset + text.len(), normalize, items);
+}
+
+/// Convert plain text into items, from characters and their source offsets.
+///
+/// This is useful when the text has been decoded from some other form, as
+/// with entities in SSML. The `end` offset is that of the end of the text.
+pub fn from_chars(
+    ttp: &TextToPhoneme,
+    chars: impl Iterator<Item = (usize, char)>,
+    end: usize,
+    normalize: bool,
+    items: &mut Vec<Item>,
+) {
+    let mut word = String::new();
+    let mut start = 0;
+    for (i, c) in chars.chain([(end, ' ')]) {
+        if c.is_ascii_alphanumeric() || (c == '\'' && !word.is_empty()) {
+            if word.is_empty() {
+                start = i;
+            }
+            word.push(c.to_ascii_lowercase());
+            continue;
+        }
+        push_word(ttp, &word, start..i, normalize, items);
+        word.clear();
+        if !normalize {
+            continue;
+        }
+        match c {
+            ',' | ';' | ':' => items.push(Item::Break(CLAUSE_BREAK)),
+            '.' | '!' | '?' => items.push(Item::Break(SENTENCE_BREAK)),
+            _ => (),
+        }
+    }
+}
+
+fn push_word(
+    ttp: &TextToPhoneme,
+    word: &str,
+    src: Range<usize>,
+    normalize: bool,
+    items: &mut Vec<Item>,
+) {
+    let word = word.trim_end_matches('\'');
+    if word.is_empty() {
+        return;
+    }
+    let mut push = |phonemes| {
+        items.push(Item::Word {
+            phonemes,
+            src: src.clone(),
+        })
+    };
+    if !normalize {
+        push(ttp.translate(&format!(" {word} ")));
+    } else if word.bytes().all(|c| c.is_ascii_digit()) {
+        match word.parse::<u64>() {
+            Ok(n) => {
+                for w in crate::numbers::cardinal(n).split(' ') {
+                    push(ttp.translate(&format!(" {w} ")));
+                }
+            }
+            Err(_) => push(crate::spell::spell(word)),
+        }
+    } else if word.bytes().any(|c| c.is_ascii_digit()) {
+        push(crate::spell::spell(word));
+    } else {
+        push(ttp.translate(&format!(" {word} ")));
+    }
+}
+
+/// The first phone among `segments` in the same word, if there is no pause
+/// before it.
+pub fn adjacent<'s, P>(segments: impl Iterator<Item = &'s Segment<P>>) -> Option<&'s P>
+where
+    P: 's,
+{
+    for segment in segments {
+        match segment {
+            Segment::Phone(phone, _) => return Some(phone),
+            Segment::Event(Event::Phone(_) | Event::Mark(_)) => {}
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Lower items to segments, using the given parser for phonemes.
+pub fn segments<P>(items: &[Item], parse: impl Fn(&str) -> Vec<P>) -> Vec<Segment<P>> {
+    let mut result = Vec::new();
+    let mut prosody = Prosody::default();
+    for item in items {
+        match item {
+            Item::Word { phonemes, src } => {
+                result.push(Segment::Event(Event::WordStart(src.clone())));
+                for phone in parse(phonemes) {
+                    result.push(Segment::Phone(phone, prosody));
+                }
+                result.push(Segment::Event(Event::WordEnd(src.clone())));
+            }
+            Item::Break(ms) => result.push(Segment::Silence(*ms)),
+            Item::Prosody(p) => prosody = *p,
+            Item::Mark(name) => result.push(Segment::Event(Event::Mark(name.clone()))),
+        }
+    }
+    result
+}
@@ -0,0 +1,169 @@
+//! Visemes, for lip sync.
+//!
+//! This is the set of 15 visemes used by MPEG-4 facial animation (as
+//! popularized by the Oculus lip sync library), which is close to the
+//! classic Preston Blair mouth shapes used in animation.
+
+use std::io::{self, Write};
+
+use crate::{phones::Phone, utterance::Event};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Viseme {
+    Sil,
+    Pp,
+    Ff,
+    Th,
+    Dd,
+    Kk,
+    Ch,
+    Ss,
+    Nn,
+    Rr,
+    Aa,
+    E,
+    Ih,
+    Oh,
+    Ou,
+}
+
+/// A viseme with start and end times, in seconds.
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub viseme: Viseme,
+}
+
+impl Viseme {
+    /// The name of the viseme in the Oculus set, as written to output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Viseme::Sil => "sil",
+            Viseme::Pp => "PP",
+            Viseme::Ff => "FF",
+            Viseme::Th => "TH",
+            Viseme::Dd => "DD",
+            Viseme::Kk => "kk",
+            Viseme::Ch => "CH",
+            Viseme::Ss => "SS",
+            Viseme::Nn => "nn",
+            Viseme::Rr => "RR",
+            Viseme::Aa => "aa",
+            Viseme::E => "E",
+            Viseme::Ih => "ih",
+            Viseme::Oh => "oh",
+            Viseme::Ou => "ou",
+        }
+    }
+
+    /// The viseme for a phoneme in the LPC inventory, by name.
+    ///
+    /// The glottal fricative h takes the shape of the following vowel, so
+    /// it's mapped to the fairly neutral E. Diphthongs are mapped according
+    /// to their onset.
+    pub fn from_ipa(name: &str) -> Viseme {
+        // Aspirated and unreleased stops look like plain ones.
+        match name.trim_end_matches(['ʰ', '\u{31a}']) {
+            "p" | "b" | "m" => Viseme::Pp,
+            "f" | "v" => Viseme::Ff,
+            "θ" | "ð" => Viseme::Th,
+            "t" | "d" | "ɾ" => Viseme::Dd,
+            "k" | "g" | "ŋ" => Viseme::Kk,
+            "tʃ" | "dʒ" | "ʃ" | "ʒ" => Viseme::Ch,
+            "s" | "z" => Viseme::Ss,
+            "n" | "l" | "ɫ" => Viseme::Nn,
+            "ɹ" | "ɚ" => Viseme::Rr,
+            "ɑ" | "a" | "æ" | "ʌ" | "aɪ" | "aʊ" | "aw" => Viseme::Aa,
+            "ɛ" | "e" | "ə" | "h" | "ɛɪ" | "eɪ" => Viseme::E,
+            "ɪ" | "i" | "j" => Viseme::Ih,
+            "ɔ" | "o" | "ɔɪ" | "oʊ" => Viseme::Oh,
+            "ʊ" | "u" | "w" | "ʍ" => Viseme::Ou,
+            _ => Viseme::Sil,
+        }
+    }
+
+    /// The viseme for a phone in the Klatt inventory.
+    ///
+    /// Diphthongs are mapped according to their onset.
+    pub fn from_phone(phone: Phone) -> Viseme {
+        match phone {
+            Phone::Bb | Phone::Pp | Phone::Mm | Phone::Em => Viseme::Pp,
+            Phone::Ff | Phone::Vv => Viseme::Ff,
+            Phone::Th | Phone::Dh => Viseme::Th,
+            Phone::Tt | Phone::Dd | Phone::Dx | Phone::Tq => Viseme::Dd,
+            Phone::Kk | Phone::Kp | Phone::Gg | Phone::Gp | Phone::Ng => Viseme::Kk,
+            Phone::Ch | Phone::Jj | Phone::Sh | Phone::Zh => Viseme::Ch,
+            Phone::Ss | Phone::Zz => Viseme::Ss,
+            Phone::Nn | Phone::En | Phone::Ll | Phone::Lx | Phone::El => Viseme::Nn,
+            Phone::Rr | Phone::Rx | Phone::Er | Phone::Axr => Viseme::Rr,
+            Phone::Aa | Phone::Ae | Phone::Ah | Phone::Aw | Phone::Ay => Viseme::Aa,
+            Phone::Ax | Phone::Axp | Phone::Eh | Phone::Ey | Phone::Exr => Viseme::E,
+            Phone::Hh | Phone::Hx => Viseme::E,
+            Phone::Ih | Phone::Ix | Phone::Ixr | Phone::Iy | Phone::Yy => Viseme::Ih,
+            Phone::Ao | Phone::Ow | Phone::Oy | Phone::Oxr => Viseme::Oh,
+            Phone::Uh | Phone::Uw | Phone::Uxr | Phone::Ww | Phone::Wh | Phone::Yu => Viseme::Ou,
+            Phone::Qq | Phone::Sil => Viseme::Sil,
+        }
+    }
+}
+
+/// Build a viseme track from timed events.
+///
+/// The mouth closes at the end of a word, unless the next word starts at
+/// the same time. Consecutive identical visemes are merged, and the track
+/// ends at `end`.
+pub fn track<P>(events: &[(f64, Event<P>)], end: f64, viseme: impl Fn(&P) -> Viseme) -> Vec<Cue> {
+    let mut cues: Vec<Cue> = vec![];
+    let mut push = |t: f64, v: Viseme| {
+        // Drop a cue which would have zero length.
+        if cues.last().is_some_and(|last| last.start == t) {
+            cues.pop();
+        }
+        if let Some(last) = cues.last_mut() {
+            if last.viseme == v {
+                last.end = end;
+                return;
+            }
+            last.end = t;
+        }
+        cues.push(Cue {
+            start: t,
+            end,
+            viseme: v,
+        });
+    };
+    for (t, event) in events {
+        match event {
+            Event::Phone(phone) => push(*t, viseme(phone)),
+            Event::WordEnd(_) => push(*t, Viseme::Sil),
+            _ => (),
+        }
+    }
+    if cues.last().is_some_and(|last| last.start >= end) {
+        cues.pop();
+    }
+    cues
+}
+
+pub fn write_csv(w: &mut impl Write, cues: &[Cue]) -> io::Result<()> {
+    writeln!(w, "start,end,viseme")?;
+    for cue in cues {
+        writeln!(w, "{:.3},{:.3},{}", cue.start, cue.end, cue.viseme.name())?;
+    }
+    Ok(())
+}
+
+pub fn write_json(w: &mut impl Write, cues: &[Cue]) -> io::Result<()> {
+    writeln!(w, "[")?;
+    for (i, cue) in cues.iter().enumerate() {
+        let sep = if i + 1 < cues.len() { "," } else { "" };
+        writeln!(
+            w,
+            "  {{\"start\": {:.3}, \"end\": {:.3}, \"viseme\": \"{}\"}}{sep}",
+            cue.start,
+            cue.end,
+            cue.viseme.name()
+        )?;
+    }
+    writeln!(w, "]")
+}
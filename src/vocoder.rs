@@ -0,0 +1,116 @@
+//! LPC analysis and resynthesis of whole recordings.
+//!
+//! Each frame is analyzed for reflection coefficients, gain and pitch, then
+//! played back through [`Synth`] with the parameters interpolated between
+//! frames. This is the best quality the LPC synthesizer can achieve, so is
+//! useful as a reference, and for testing changes to analysis.
+
+use crate::{
+    lpc::{self, Reflector},
+    pitch::{self, PitchTracker},
+    synth::{Params, Synth},
+};
+
+/// Spacing of frames at 16 kHz, in samples.
+pub const HOP: usize = 160;
+/// Length of the analysis window at 16 kHz, in samples.
+pub const WINDOW: usize = 640;
+/// Number of interpolation steps per frame.
+const STEPS: usize = 8;
+
+/// Analyze a recording into one set of parameters per frame.
+///
+/// Frames are `hop` samples apart, and each is analyzed over a window
+/// centered on the frame, which needs to span two periods of the lowest
+/// pitch. The gain is set so that the output of [`synthesize`] has the same
+/// level as the input. Silent frames have zero gain.
+pub fn analyze(
+    samples: &[f64],
+    sample_rate: u32,
+    preemph: f64,
+    hop: usize,
+    window: usize,
+) -> Vec<Params> {
+    if samples.len() < window {
+        return vec![];
+    }
+    let emphasized = lpc::preemph(samples, preemph);
+    let tracker = PitchTracker::new(sample_rate);
+    let windows = (0..samples.len().div_ceil(hop))
+        .map(|i| {
+            let center = i * hop + hop / 2;
+            let start = center
+                .saturating_sub(window / 2)
+                .min(samples.len() - window);
+            start..start + window
+        })
+        .collect::<Vec<_>>();
+    let mut periods = windows
+        .iter()
+        .map(|window| {
+            let buf = &samples[window.clone()];
+            tracker.analyze(buf, &Reflector::new(buf))
+        })
+        .collect::<Vec<_>>();
+    pitch::smooth(&mut periods);
+    windows
+        .into_iter()
+        .zip(periods)
+        .map(|(window, period)| {
+            // Voiced frames are analyzed with pre-emphasis, which the glottal
+            // pulse of the synthesizer undoes.
+            let buf = if period.is_some() {
+                &emphasized
+            } else {
+                samples
+            };
+            let coeffs = Reflector::new(&buf[window]);
+            if !coeffs.rms().is_finite() {
+                return Params {
+                    k: vec![0.0; coeffs.ks().len()],
+                    period: 0.0,
+                    rms: 0.0,
+                    noise: 0.0,
+                };
+            }
+            // Unvoiced excitation has unit RMS, while a pulse train has an
+            // RMS of about one over the square root of the period.
+            let period = period.unwrap_or(0);
+            let rms = coeffs.rms() * (period.max(1) as f64).sqrt();
+            Params {
+                k: coeffs.ks().into(),
+                period: period as f64,
+                rms,
+                noise: 0.0,
+            }
+        })
+        .collect()
+}
+
+/// Synthesize frames from [`analyze`], `hop` samples apart.
+///
+/// Parameters are interpolated in steps within each frame, except across a
+/// change in voicing.
+pub fn synthesize(frames: &[Params], hop: usize) -> Vec<f64> {
+    let Some(first) = frames.first() else {
+        return vec![];
+    };
+    let mut synth = Synth::new(first.k.len());
+    let mut result = Vec::with_capacity(frames.len() * hop);
+    for (i, frame) in frames.iter().enumerate() {
+        let next = frames.get(i + 1).unwrap_or(frame);
+        let interpolate = (frame.period == 0.0) == (next.period == 0.0);
+        for step in 0..STEPS {
+            let params = if interpolate {
+                frame.lerp(next, step as f64 / STEPS as f64)
+            } else {
+                frame.clone()
+            };
+            let len = (step + 1) * hop / STEPS - step * hop / STEPS;
+            for _ in 0..len {
+                result.push(synth.get_sample(&params));
+            }
+        }
+    }
+    result
+}
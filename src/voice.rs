@@ -0,0 +1,817 @@
+//! Voices, which can be loaded at runtime.
+//!
+//! A voice is either a set of LPC phonemes with the transitions between them
+//! and rules for their allophones, which are lowered into segments for
+//! [`Sequence`], or targets and durations
+//! of Klatt phones, for [`Phonet`]. Voices are stored as TOML, or in a compact binary form for
+//! embedded use, in which reflection coefficients are quantized to 16 bits.
+//!
+//! [`Sequence`]: crate::sequence::Sequence
+//! [`Phonet`]: crate::phonet::Phonet
+
+use std::{borrow::Cow, fmt, io};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    allophone,
+    phonemes::{Kind, PHONEMES, Phoneme, Stop},
+    phones::{
+        ParamTarget, ParamTargetGlide, Phone, PhoneDuration, nonvocalic_target, phone_duration,
+        vocalic_target, vocalic_target_glide,
+    },
+    synth::{Excitation, Noise, Pulse},
+    transition::{self, Rule, Shape, Transition},
+    utterance::Segment,
+};
+
+pub enum Voice {
+    Lpc(LpcVoice),
+    Klatt(KlattVoice),
+}
+
+/// A set of named LPC phonemes.
+pub struct LpcVoice {
+    phonemes: Vec<(String, Phoneme)>,
+    transitions: Vec<Rule>,
+    allophones: Vec<allophone::Rule>,
+    excitation: Excitation,
+}
+
+/// The data for one Klatt phone.
+///
+/// A phone without a target or duration can't be spoken.
+#[derive(Clone, Default)]
+pub struct PhoneData {
+    pub target: Option<ParamTarget>,
+    pub glide: Option<ParamTargetGlide>,
+    pub duration: Option<PhoneDuration>,
+}
+
+/// Targets and durations for Klatt phones.
+pub struct KlattVoice {
+    /// Indexed by phone.
+    phones: Vec<PhoneData>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    /// A binary voice file that is truncated or otherwise malformed.
+    Binary,
+    UnknownKind(String),
+    UnknownPhone(String),
+    /// A binary voice file of a version this can't read.
+    Version(u8),
+    /// A voice with more entries, or longer names, than the binary form can
+    /// hold.
+    TooLarge,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Toml(e) => write!(f, "{e}"),
+            Error::Binary => write!(f, "invalid binary voice"),
+            Error::UnknownKind(kind) => write!(f, "unknown phoneme kind \"{kind}\""),
+            Error::UnknownPhone(name) => write!(f, "unknown phone \"{name}\""),
+            Error::Version(v) => write!(f, "unsupported binary voice version {v}"),
+            Error::TooLarge => write!(f, "voice too large for the binary form"),
+        }
+    }
+}
+
+impl LpcVoice {
+    /// A voice with the built-in transitions and allophone rules.
+    pub fn new(phonemes: Vec<(String, Phoneme)>) -> Self {
+        Self {
+            phonemes,
+            transitions: transition::builtin(),
+            allophones: allophone::builtin(),
+            excitation: Excitation::default(),
+        }
+    }
+
+    /// The voice compiled into the library.
+    pub fn builtin() -> Self {
+        let phonemes = PHONEMES
+            .iter()
+            .map(|(name, ph)| (name.to_string(), ph.clone()))
+            .collect();
+        Self::new(phonemes)
+    }
+
+    pub fn get(&self, s: &str) -> Option<&Phoneme> {
+        self.phonemes
+            .iter()
+            .find(|(name, _)| name == s)
+            .map(|(_, ph)| ph)
+    }
+
+    /// The phonemes of this voice, with their names.
+    #[cfg(feature = "rpoly")]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Phoneme)> {
+        self.phonemes.iter().map(|(name, ph)| (name.as_str(), ph))
+    }
+
+    /// The transition between two phonemes of this voice, if they blend.
+    pub fn transition(&self, from: &Phoneme, to: &Phoneme) -> Option<&Transition> {
+        transition::find(
+            &self.transitions,
+            (self.name(from), from),
+            (self.name(to), to),
+        )
+    }
+
+    /// The sources exciting the filter.
+    pub fn excitation(&self) -> Excitation {
+        self.excitation
+    }
+
+    /// Replace phonemes of this voice with allophones, according to its rules.
+    pub fn choose_allophones<'a>(
+        &'a self,
+        segments: Vec<Segment<&'a Phoneme>>,
+    ) -> Vec<Segment<&'a Phoneme>> {
+        allophone::choose(&self.allophones, self, segments)
+    }
+
+    /// The name of a phoneme in this voice, the inverse of [`LpcVoice::get`].
+    pub fn name(&self, phoneme: &Phoneme) -> &str {
+        for (name, ph) in &self.phonemes {
+            if core::ptr::eq(ph, phoneme) {
+                return name;
+            }
+        }
+        "?"
+    }
+
+    /// Parse a string of concise IPA into phonemes of this voice.
+    ///
+    /// Unknown symbols are skipped.
+    pub fn parse(&self, s: &str) -> Vec<&Phoneme> {
+        let mut result = Vec::new();
+        let mut i = 0;
+        while i < s.len() {
+            for j in (1..=3).rev() {
+                let mut end = i;
+                for _ in 0..j {
+                    end = s.ceil_char_boundary(end + 1);
+                }
+                if let Some(phoneme) = self.get(&s[i..end]) {
+                    result.push(phoneme);
+                    i = end;
+                    break;
+                }
+                if j == 1 {
+                    i = end;
+                }
+            }
+        }
+        result
+    }
+}
+
+impl KlattVoice {
+    /// The voice compiled into the library, from the tables in the book.
+    pub fn builtin() -> Self {
+        let phones = Phone::ALL
+            .iter()
+            .map(|&phone| PhoneData {
+                target: vocalic_target(phone)
+                    .or_else(|| nonvocalic_target(phone))
+                    .cloned(),
+                glide: vocalic_target_glide(phone).cloned(),
+                duration: phone_duration(phone),
+            })
+            .collect();
+        Self { phones }
+    }
+
+    pub fn target(&self, phone: Phone) -> Option<&ParamTarget> {
+        self.phones[phone as usize].target.as_ref()
+    }
+
+    pub fn glide(&self, phone: Phone) -> Option<&ParamTargetGlide> {
+        self.phones[phone as usize].glide.as_ref()
+    }
+
+    pub fn duration(&self, phone: Phone) -> Option<PhoneDuration> {
+        self.phones[phone as usize].duration
+    }
+
+    #[cfg(feature = "rpoly")]
+    pub fn phone_mut(&mut self, phone: Phone) -> &mut PhoneData {
+        &mut self.phones[phone as usize]
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum VoiceFile {
+    Lpc {
+        #[serde(default, skip_serializing_if = "is_default")]
+        excitation: Excitation,
+        phonemes: Vec<LpcEntry>,
+        /// The built-in transitions are used if there are none.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        transitions: Vec<Rule>,
+        /// As are the built-in allophone rules.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        allophones: Vec<allophone::Rule>,
+    },
+    Klatt {
+        phones: Vec<KlattEntry>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct LpcEntry {
+    name: String,
+    kind: String,
+    voiced: bool,
+    rms: f64,
+    ks: Vec<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duration: Option<PhoneDuration>,
+    /// Coefficients at the end of a diphthong.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    glide: Option<Vec<f64>>,
+    /// Noise mixed into voicing, relative to the voicing.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    noise: f64,
+    /// The release of a plosive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stop: Option<Stop>,
+}
+
+fn is_zero(x: &f64) -> bool {
+    *x == 0.0
+}
+
+fn is_default<T: Default + PartialEq>(x: &T) -> bool {
+    *x == T::default()
+}
+
+#[derive(Serialize, Deserialize)]
+struct KlattEntry {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duration: Option<PhoneDuration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    glide: Option<ParamTargetGlide>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target: Option<ParamTarget>,
+}
+
+/// The start of a binary voice file, which is followed by its version.
+const MAGIC: &[u8; 3] = b"PTV";
+/// The version of the binary form, to be incremented when it changes.
+const VERSION: u8 = 1;
+const TYPE_LPC: u8 = 0;
+const TYPE_KLATT: u8 = 1;
+
+const HAS_TARGET: u8 = 1;
+const HAS_GLIDE: u8 = 2;
+const HAS_DURATION: u8 = 4;
+
+impl Voice {
+    /// The total inherent duration of a string of phonemes in concise IPA,
+    /// in ms.
+    pub fn inherent_duration(&self, ipa: &str) -> u32 {
+        let durations: Vec<PhoneDuration> = match self {
+            Voice::Lpc(voice) => (voice.parse(ipa).iter())
+                .map(|ph| ph.duration.unwrap_or(ph.kind.default_duration()))
+                .collect(),
+            Voice::Klatt(voice) => (Phone::parse_ipa(ipa).into_iter())
+                .filter_map(|phone| voice.duration(phone))
+                .collect(),
+        };
+        durations.iter().map(|d| d.inherent_duration as u32).sum()
+    }
+
+    /// Load a voice file, in either TOML or binary form.
+    pub fn load(filename: &str) -> Result<Voice, Error> {
+        let data = std::fs::read(filename).map_err(Error::Io)?;
+        if data.starts_with(MAGIC) {
+            Voice::from_bytes(&data)
+        } else {
+            Voice::from_toml(str::from_utf8(&data).map_err(|_| Error::Binary)?)
+        }
+    }
+
+    pub fn from_toml(s: &str) -> Result<Voice, Error> {
+        match toml::from_str(s).map_err(Error::Toml)? {
+            VoiceFile::Lpc {
+                excitation,
+                phonemes,
+                transitions,
+                allophones,
+            } => {
+                let mut result = vec![];
+                for entry in phonemes {
+                    let kind =
+                        Kind::from_name(&entry.kind).ok_or(Error::UnknownKind(entry.kind))?;
+                    let phoneme = Phoneme {
+                        kind,
+                        voiced: entry.voiced,
+                        ks: Cow::Owned(entry.ks),
+                        rms: entry.rms,
+                        noise: entry.noise,
+                        duration: entry.duration,
+                        glide: entry.glide.map(Cow::Owned),
+                        stop: entry.stop,
+                    };
+                    result.push((entry.name, phoneme));
+                }
+                let mut voice = LpcVoice::new(result);
+                if !transitions.is_empty() {
+                    voice.transitions = transitions;
+                }
+                if !allophones.is_empty() {
+                    voice.allophones = allophones;
+                }
+                voice.excitation = excitation;
+                Ok(Voice::Lpc(voice))
+            }
+            VoiceFile::Klatt { phones } => {
+                let mut result = vec![PhoneData::default(); Phone::ALL.len()];
+                for entry in phones {
+                    let phone = Phone::parse(&entry.name).ok_or(Error::UnknownPhone(entry.name))?;
+                    result[phone as usize] = PhoneData {
+                        target: entry.target,
+                        glide: entry.glide,
+                        duration: entry.duration,
+                    };
+                }
+                Ok(Voice::Klatt(KlattVoice { phones: result }))
+            }
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        let file = match self {
+            Voice::Lpc(voice) => VoiceFile::Lpc {
+                excitation: voice.excitation,
+                phonemes: voice
+                    .phonemes
+                    .iter()
+                    .map(|(name, ph)| LpcEntry {
+                        name: name.clone(),
+                        kind: ph.kind.name().into(),
+                        voiced: ph.voiced,
+                        rms: ph.rms,
+                        ks: ph.ks.to_vec(),
+                        duration: ph.duration,
+                        glide: ph.glide.as_ref().map(|ks| ks.to_vec()),
+                        noise: ph.noise,
+                        stop: ph.stop,
+                    })
+                    .collect(),
+                transitions: voice.transitions.clone(),
+                allophones: voice.allophones.clone(),
+            },
+            Voice::Klatt(voice) => VoiceFile::Klatt {
+                phones: Phone::ALL
+                    .iter()
+                    .zip(&voice.phones)
+                    .filter(|(_, data)| data.target.is_some() || data.duration.is_some())
+                    .map(|(phone, data)| KlattEntry {
+                        name: phone.name(),
+                        duration: data.duration,
+                        glide: data.glide.clone(),
+                        target: data.target.clone(),
+                    })
+                    .collect(),
+            },
+        };
+        toml::to_string(&file).unwrap()
+    }
+
+    /// Decode the compact binary form.
+    pub fn from_bytes(data: &[u8]) -> Result<Voice, Error> {
+        let mut r = Reader(data.strip_prefix(MAGIC).ok_or(Error::Binary)?);
+        let version = r.u8()?;
+        if version != VERSION {
+            return Err(Error::Version(version));
+        }
+        let voice = match r.u8()? {
+            TYPE_LPC => {
+                let n = r.u16()?;
+                let mut result = vec![];
+                for _ in 0..n {
+                    let name = r.str()?;
+                    let kind = match r.u8()? {
+                        0 => Kind::Vowel,
+                        1 => Kind::Fricative,
+                        2 => Kind::Nasal,
+                        3 => Kind::Plosive,
+                        4 => Kind::Affricate,
+                        _ => return Err(Error::Binary),
+                    };
+                    let voiced = r.u8()? != 0;
+                    let rms = r.f32()?;
+                    let ks = r.ks()?;
+                    let phoneme = Phoneme {
+                        kind,
+                        voiced,
+                        ks: Cow::Owned(ks),
+                        rms,
+                        noise: 0.0,
+                        duration: None,
+                        glide: None,
+                        stop: None,
+                    };
+                    result.push((name.to_string(), phoneme));
+                }
+                let mut voice = LpcVoice::new(result);
+                let n = r.u8()?;
+                voice.transitions = (0..n).map(|_| r.rule()).collect::<Result<_, _>>()?;
+                // Durations, with zero for none.
+                for (_, phoneme) in &mut voice.phonemes {
+                    let duration = PhoneDuration {
+                        min_duration: r.u16()?,
+                        inherent_duration: r.u16()?,
+                    };
+                    phoneme.duration = Some(duration).filter(|d| d.inherent_duration > 0);
+                }
+                // The ends of diphthongs, with no coefficients for none.
+                for (_, phoneme) in &mut voice.phonemes {
+                    let ks = r.ks()?;
+                    phoneme.glide = (!ks.is_empty()).then_some(Cow::Owned(ks));
+                }
+                let n = r.u8()?;
+                voice.allophones = (0..n)
+                    .map(|_| r.allophone_rule())
+                    .collect::<Result<_, _>>()?;
+                // The noise mixed into voicing.
+                for (_, phoneme) in &mut voice.phonemes {
+                    phoneme.noise = r.f32()?;
+                }
+                voice.excitation.pulse = match r.u8()? {
+                    0 => Pulse::Impulse,
+                    1 => Pulse::Chirp,
+                    2 => Pulse::Rosenberg,
+                    _ => return Err(Error::Binary),
+                };
+                voice.excitation.noise = match r.u8()? {
+                    0 => Noise::White,
+                    1 => Noise::Tilted,
+                    _ => return Err(Error::Binary),
+                };
+                // The releases of plosives, each after a flag.
+                for (_, phoneme) in &mut voice.phonemes {
+                    if r.u8()? != 0 {
+                        phoneme.stop = Some(Stop {
+                            burst: r.f32()?,
+                            release: r.f32()?,
+                        });
+                    }
+                }
+                Voice::Lpc(voice)
+            }
+            TYPE_KLATT => {
+                let mut result = vec![PhoneData::default(); Phone::ALL.len()];
+                let n = r.u8()?;
+                for _ in 0..n {
+                    let ix = r.u8()? as usize;
+                    let flags = r.u8()?;
+                    let data = result.get_mut(ix).ok_or(Error::Binary)?;
+                    if flags & HAS_TARGET != 0 {
+                        let b = r.bytes(10)?;
+                        data.target = Some(ParamTarget {
+                            av: b[0],
+                            avc: b[1],
+                            asp: b[2],
+                            af: b[3],
+                            a2: b[4],
+                            a3: b[5],
+                            a4: b[6],
+                            a5: b[7],
+                            a6: b[8],
+                            ab: b[9],
+                            f1: r.u16()?,
+                            f2: r.u16()?,
+                            f3: r.u16()?,
+                            f4: r.u16()?,
+                            b1: r.u16()?,
+                            b2: r.u16()?,
+                            b3: r.u16()?,
+                        });
+                    }
+                    if flags & HAS_GLIDE != 0 {
+                        data.glide = Some(ParamTargetGlide {
+                            f1: r.u16()?,
+                            f2: r.u16()?,
+                            f3: r.u16()?,
+                        });
+                    }
+                    if flags & HAS_DURATION != 0 {
+                        data.duration = Some(PhoneDuration {
+                            min_duration: r.u16()?,
+                            inherent_duration: r.u16()?,
+                        });
+                    }
+                }
+                Voice::Klatt(KlattVoice { phones: result })
+            }
+            _ => return Err(Error::Binary),
+        };
+        if !r.0.is_empty() {
+            return Err(Error::Binary);
+        }
+        Ok(voice)
+    }
+
+    /// Encode in the compact binary form.
+    ///
+    /// All values are little-endian. Reflection coefficients are stored as
+    /// 16 bit fixed point, and RMS as a 32 bit float.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut w = MAGIC.to_vec();
+        w.push(VERSION);
+        match self {
+            Voice::Lpc(voice) => {
+                w.push(TYPE_LPC);
+                w.extend(narrow::<u16>(voice.phonemes.len())?.to_le_bytes());
+                for (name, ph) in &voice.phonemes {
+                    write_str(&mut w, name)?;
+                    w.push(ph.kind as u8);
+                    w.push(ph.voiced as u8);
+                    w.extend((ph.rms as f32).to_bits().to_le_bytes());
+                    write_ks(&mut w, &ph.ks)?;
+                }
+                w.push(narrow(voice.transitions.len())?);
+                for rule in &voice.transitions {
+                    for pattern in [&rule.from, &rule.to] {
+                        write_str(&mut w, pattern)?;
+                    }
+                    let t = &rule.transition;
+                    w.push(t.shape as u8);
+                    for x in [t.lead, t.duration, t.next_start, t.overlap] {
+                        w.extend((x as f32).to_bits().to_le_bytes());
+                    }
+                }
+                for (_, ph) in &voice.phonemes {
+                    let d = ph
+                        .duration
+                        .map_or([0, 0], |d| [d.min_duration, d.inherent_duration]);
+                    for x in d {
+                        w.extend(x.to_le_bytes());
+                    }
+                }
+                for (_, ph) in &voice.phonemes {
+                    write_ks(&mut w, ph.glide.as_deref().unwrap_or_default())?;
+                }
+                w.push(narrow(voice.allophones.len())?);
+                for rule in &voice.allophones {
+                    let (prev, next) = (rule.prev.as_deref(), rule.next.as_deref());
+                    for s in [&rule.phoneme, &rule.allophone] {
+                        write_str(&mut w, s)?;
+                    }
+                    // An empty pattern is missing.
+                    for s in [prev, next] {
+                        write_str(&mut w, s.unwrap_or_default())?;
+                    }
+                }
+                for (_, ph) in &voice.phonemes {
+                    w.extend((ph.noise as f32).to_bits().to_le_bytes());
+                }
+                w.push(voice.excitation.pulse as u8);
+                w.push(voice.excitation.noise as u8);
+                for (_, ph) in &voice.phonemes {
+                    w.push(ph.stop.is_some() as u8);
+                    if let Some(stop) = ph.stop {
+                        for x in [stop.burst, stop.release] {
+                            w.extend((x as f32).to_bits().to_le_bytes());
+                        }
+                    }
+                }
+            }
+            Voice::Klatt(voice) => {
+                w.push(TYPE_KLATT);
+                let count_ix = w.len();
+                w.push(0);
+                for (ix, data) in voice.phones.iter().enumerate() {
+                    let mut flags = 0;
+                    if data.target.is_some() {
+                        flags |= HAS_TARGET;
+                    }
+                    if data.glide.is_some() {
+                        flags |= HAS_GLIDE;
+                    }
+                    if data.duration.is_some() {
+                        flags |= HAS_DURATION;
+                    }
+                    if flags == 0 {
+                        continue;
+                    }
+                    w[count_ix] += 1;
+                    w.extend([ix as u8, flags]);
+                    if let Some(t) = &data.target {
+                        w.extend([t.av, t.avc, t.asp, t.af, t.a2, t.a3, t.a4, t.a5, t.a6, t.ab]);
+                        for x in [t.f1, t.f2, t.f3, t.f4, t.b1, t.b2, t.b3] {
+                            w.extend(x.to_le_bytes());
+                        }
+                    }
+                    if let Some(g) = &data.glide {
+                        for x in [g.f1, g.f2, g.f3] {
+                            w.extend(x.to_le_bytes());
+                        }
+                    }
+                    if let Some(d) = data.duration {
+                        for x in [d.min_duration, d.inherent_duration] {
+                            w.extend(x.to_le_bytes());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(w)
+    }
+}
+
+/// Convert a count or length to the width of its field in the binary form.
+fn narrow<T: TryFrom<usize>>(n: usize) -> Result<T, Error> {
+    T::try_from(n).map_err(|_| Error::TooLarge)
+}
+
+fn write_str(w: &mut Vec<u8>, s: &str) -> Result<(), Error> {
+    w.push(narrow(s.len())?);
+    w.extend(s.as_bytes());
+    Ok(())
+}
+
+fn write_ks(w: &mut Vec<u8>, ks: &[f64]) -> Result<(), Error> {
+    w.push(narrow(ks.len())?);
+    for k in ks {
+        let k = (k * 32768.).round().clamp(-32768., 32767.) as i16;
+        w.extend(k.to_le_bytes());
+    }
+    Ok(())
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < n {
+            return Err(Error::Binary);
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    /// Reflection coefficients, quantized to 16 bits.
+    fn ks(&mut self) -> Result<Vec<f64>, Error> {
+        let n = self.u8()?;
+        (0..n)
+            .map(|_| Ok(self.u16()? as i16 as f64 / 32768.))
+            .collect()
+    }
+
+    fn f32(&mut self) -> Result<f64, Error> {
+        Ok(f32::from_bits(self.u32()?) as f64)
+    }
+
+    fn str(&mut self) -> Result<&'a str, Error> {
+        let len = self.u8()? as usize;
+        str::from_utf8(self.bytes(len)?).map_err(|_| Error::Binary)
+    }
+
+    fn allophone_rule(&mut self) -> Result<allophone::Rule, Error> {
+        let phoneme = self.str()?.to_string();
+        let allophone = self.str()?.to_string();
+        let mut pattern = || -> Result<_, Error> {
+            let s = self.str()?;
+            Ok((!s.is_empty()).then(|| s.to_string()))
+        };
+        Ok(allophone::Rule {
+            phoneme,
+            allophone,
+            prev: pattern()?,
+            next: pattern()?,
+        })
+    }
+
+    fn rule(&mut self) -> Result<Rule, Error> {
+        let from = self.str()?.to_string();
+        let to = self.str()?.to_string();
+        let shape = match self.u8()? {
+            0 => Shape::Linear,
+            1 => Shape::Smooth,
+            _ => return Err(Error::Binary),
+        };
+        let transition = Transition {
+            lead: self.f32()?,
+            duration: self.f32()?,
+            next_start: self.f32()?,
+            overlap: self.f32()?,
+            shape,
+        };
+        Ok(Rule {
+            from,
+            to,
+            transition,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builtins() -> [Voice; 2] {
+        [
+            Voice::Lpc(LpcVoice::builtin()),
+            Voice::Klatt(KlattVoice::builtin()),
+        ]
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        for voice in builtins() {
+            let toml = voice.to_toml();
+            assert_eq!(Voice::from_toml(&toml).unwrap().to_toml(), toml);
+        }
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        for voice in builtins() {
+            let bytes = voice.to_bytes().unwrap();
+            assert_eq!(
+                Voice::from_bytes(&bytes).unwrap().to_bytes().unwrap(),
+                bytes
+            );
+        }
+    }
+
+    #[test]
+    fn binary_through_toml() {
+        // Binary is lossy, but what it keeps survives TOML unchanged.
+        for voice in builtins() {
+            let bytes = voice.to_bytes().unwrap();
+            let toml = Voice::from_bytes(&bytes).unwrap().to_toml();
+            assert_eq!(Voice::from_toml(&toml).unwrap().to_bytes().unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn trailing_binary() {
+        for voice in builtins() {
+            let mut bytes = voice.to_bytes().unwrap();
+            bytes.push(0);
+            assert!(matches!(Voice::from_bytes(&bytes), Err(Error::Binary)));
+        }
+    }
+
+    #[test]
+    fn too_large_for_binary() {
+        let mut voice = LpcVoice::builtin();
+        let rule = voice.transitions[0].clone();
+        voice.transitions = vec![rule; 256];
+        assert!(matches!(Voice::Lpc(voice).to_bytes(), Err(Error::TooLarge)));
+        let mut voice = LpcVoice::builtin();
+        voice.phonemes[0].0 = "x".repeat(256);
+        assert!(matches!(Voice::Lpc(voice).to_bytes(), Err(Error::TooLarge)));
+    }
+
+    #[test]
+    fn unknown_version() {
+        let mut bytes = Voice::Lpc(LpcVoice::builtin()).to_bytes().unwrap();
+        bytes[MAGIC.len()] = VERSION + 1;
+        assert!(matches!(
+            Voice::from_bytes(&bytes),
+            Err(Error::Version(v)) if v == VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn truncated_binary() {
+        for voice in builtins() {
+            let bytes = voice.to_bytes().unwrap();
+            for len in [0, MAGIC.len(), MAGIC.len() + 1, bytes.len() / 2] {
+                assert!(matches!(
+                    Voice::from_bytes(&bytes[..len]),
+                    Err(Error::Binary)
+                ));
+            }
+        }
+    }
+}